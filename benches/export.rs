@@ -0,0 +1,70 @@
+//! Compares write_interleaved with writing samples one at a time, run with `cargo bench`
+
+extern crate ez_io;
+extern crate pcm;
+
+use ez_io::WriteE;
+use pcm::{Endianness, Frame, PCMParameters, Sample, PCM};
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+/// Number of times each export is repeated
+const RUNS: u32 = 10;
+
+/// Writes samples one at a time, as raw exports used to
+fn write_per_sample<W: Write>(pcm: &PCM, writer: &mut W) {
+    for frame in &pcm.frames {
+        for sample in &frame.samples {
+            match sample {
+                Sample::Signed16bits(s) => writer.write_le_to_i16(*s).unwrap(),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+/// Returns the average time taken by an export to a buffered writer, as a file would be written to
+fn time<F: FnMut(&mut BufWriter<io::Sink>)>(mut export: F) -> Duration {
+    let mut output = BufWriter::new(io::sink());
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        export(&mut output);
+    }
+    output.flush().unwrap();
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    // Ten minutes of stereo CD audio
+    let pcm = PCM {
+        parameters: PCMParameters::new(44100, 2, Sample::Signed16bits(0)).unwrap(),
+        loop_info: None,
+        markers: Vec::new(),
+        broadcast_info: None,
+        tags: BTreeMap::new(),
+        frames: (0..44100 * 600)
+            .map(|i| Frame {
+                samples: vec![
+                    Sample::Signed16bits(i as i16),
+                    Sample::Signed16bits(!i as i16),
+                ],
+            })
+            .collect(),
+    };
+    let size = pcm.get_audio_size() as f64 / 1_000_000f64;
+    for &(name, duration) in &[
+        ("per sample", time(|output| write_per_sample(&pcm, output))),
+        (
+            "write_interleaved",
+            time(|output| pcm.write_interleaved(output, Endianness::Little).unwrap()),
+        ),
+    ] {
+        println!(
+            "{}: {:?}, {:.0} MB/s",
+            name,
+            duration,
+            size / duration.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use ez_io::{ReadE, WriteE};
+use magic_number::check_magic_number;
+use sample_types::I24;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use {read_bytes, BTreeMap, Frame, PCMError, PCMParameters, Result, Sample, PCM};
+
+/// Contents of the common chunk, needed to decode the audio data
+struct AiffCommon {
+    parameters: PCMParameters,
+    nb_frames: u32,
+    bits_per_sample: u16,
+}
+
+impl AiffCommon {
+    /// Reads the contents of a common chunk
+    fn import<R: Read>(reader: &mut R) -> Result<AiffCommon> {
+        let nb_channels = reader.read_be_to_u16()?;
+        let nb_frames = reader.read_be_to_u32()?;
+        let bits_per_sample = reader.read_be_to_u16()?;
+        let mut sample_rate = [0u8; 10];
+        reader.read_exact(&mut sample_rate)?;
+        Ok(AiffCommon {
+            parameters: PCMParameters::new(
+                extended_to_u32(sample_rate),
+                nb_channels,
+                Sample::aiff_from_bps(bits_per_sample)?,
+            )?,
+            nb_frames,
+            bits_per_sample,
+        })
+    }
+    /// Size of a frame in bytes
+    fn block_size(&self) -> usize {
+        (self.bits_per_sample as usize).div_ceil(8) * self.parameters.nb_channels as usize
+    }
+    /// Converts big-endian audio data into frames
+    fn decode(&self, data: &[u8]) -> Vec<Frame> {
+        let sample_size = (self.bits_per_sample as usize).div_ceil(8);
+        data.chunks(self.block_size())
+            .filter(|f| f.len() == self.block_size())
+            .take(self.nb_frames as usize)
+            .map(|f| Frame {
+                samples: f
+                    .chunks(sample_size)
+                    .map(|s| match sample_size {
+                        // 8 bits samples are signed in AIFF
+                        1 => Sample::from(s[0] as i8),
+                        2 => Sample::Signed16bits(i16::from(s[0] as i8) << 8 | i16::from(s[1])),
+                        3 => Sample::Signed24bits(I24 {
+                            value: i32::from(s[0] as i8) << 16 | i32::from(s[1]) << 8
+                                | i32::from(s[2]),
+                        }),
+                        _ => Sample::Signed32bits(
+                            i32::from(s[0] as i8) << 24 | i32::from(s[1]) << 16
+                                | i32::from(s[2]) << 8 | i32::from(s[3]),
+                        ),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Reads the ID and size of the next chunk in a FORM, returns None when there are no chunks left
+fn next_chunk<R: Read + Seek>(reader: &mut R, form_end: u64) -> Result<Option<([u8; 4], u32)>> {
+    if reader.seek(SeekFrom::Current(0))? + 8 > form_end {
+        return Ok(None);
+    }
+    let mut chunk_id = [0u8; 4];
+    match reader.read_exact(&mut chunk_id) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Some((chunk_id, reader.read_be_to_u32()?)))
+}
+
+/// Reads the samples of a sound data chunk, skipping the offset before them
+fn read_sound_data<R: Read + Seek>(reader: &mut R, chunk_size: u32) -> Result<Vec<u8>> {
+    let offset = reader.read_be_to_u32()?;
+    let _block_size = reader.read_be_to_u32()?;
+    let data_size = match chunk_size.checked_sub(offset.saturating_add(8)) {
+        Some(size) => size,
+        None => return Err(PCMError::InvalidHeader("sound data offset past its chunk")),
+    };
+    reader.seek(SeekFrom::Current(i64::from(offset)))?;
+    read_bytes(reader, u64::from(data_size))
+}
+
+/// Converts an 80 bits IEEE 754 extended precision float into an integer sample rate
+fn extended_to_u32(bytes: [u8; 10]) -> u32 {
+    let exponent = (i32::from(bytes[0] & 0x7F) << 8 | i32::from(bytes[1])) - 16383;
+    let mut mantissa = 0u64;
+    for byte in &bytes[2..] {
+        mantissa = mantissa << 8 | u64::from(*byte);
+    }
+    if bytes[0] & 0x80 != 0 || exponent < 0 {
+        return 0;
+    }
+    if exponent > 31 {
+        return <u32>::max_value();
+    }
+    // The mantissa has its integer bit explicitly set at bit 63
+    (mantissa >> (63 - exponent)) as u32
+}
+
+/// Converts a sample rate into an 80 bits IEEE 754 extended precision float
+fn u32_to_extended(value: u32) -> [u8; 10] {
+    let mut bytes = [0u8; 10];
+    if value == 0 {
+        return bytes;
+    }
+    let exponent = 31 - value.leading_zeros();
+    let biased_exponent = (exponent + 16383) as u16;
+    bytes[0] = (biased_exponent >> 8) as u8;
+    bytes[1] = biased_exponent as u8;
+    let mantissa = u64::from(value) << (63 - exponent);
+    for (id, byte) in bytes[2..].iter_mut().enumerate() {
+        *byte = (mantissa >> (56 - id * 8)) as u8;
+    }
+    bytes
+}
+
+impl PCM {
+    /// Imports an AIFF file, samples are read as big-endian
+    pub fn aiff_import_file<R: Read + Seek>(reader: &mut R) -> Result<PCM> {
+        let form_start = reader.seek(SeekFrom::Current(0))?;
+        check_magic_number(reader, vec![b'F', b'O', b'R', b'M'])?;
+        let form_size = reader.read_be_to_u32()?;
+        check_magic_number(reader, vec![b'A', b'I', b'F', b'F'])?;
+        let form_end = form_start + 8 + u64::from(form_size);
+        let mut common = None;
+        let mut data = None;
+        while let Some((chunk_id, chunk_size)) = next_chunk(reader, form_end)? {
+            let chunk_end = reader.seek(SeekFrom::Current(0))? + u64::from(chunk_size);
+            let chunk_offset = chunk_end - u64::from(chunk_size) - 8;
+            match &chunk_id {
+                b"COMM" => {
+                    common = Some(
+                        AiffCommon::import(reader)
+                            .map_err(|e| e.in_chunk(chunk_id, chunk_offset))?,
+                    )
+                }
+                b"SSND" => {
+                    data = Some(
+                        read_sound_data(reader, chunk_size)
+                            .map_err(|e| e.in_chunk(chunk_id, chunk_offset))?,
+                    )
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even size
+            reader.seek(SeekFrom::Start(chunk_end + u64::from(chunk_size & 1)))?;
+        }
+        let common = match common {
+            Some(c) => c,
+            None => return Err(PCMError::MissingChunk(*b"COMM")),
+        };
+        let frames = match data {
+            Some(ref d) => common.decode(d),
+            None if common.nb_frames == 0 => Vec::new(),
+            None => return Err(PCMError::MissingChunk(*b"SSND")),
+        };
+        Ok(PCM {
+            parameters: common.parameters,
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames,
+        })
+    }
+    /// Exports an AIFF file from a PCM, samples are written as big-endian
+    pub fn aiff_export_file<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        self.validate()?;
+        match self.parameters.sample_type {
+            Sample::Unsigned8bits(_)
+            | Sample::Signed16bits(_)
+            | Sample::Signed24bits(_)
+            | Sample::Signed32bits(_) => {}
+            ref s => return Err(PCMError::UnsupportedSampleType(s.clone())),
+        }
+        // Sizes of all chunks, the sound data chunk has an offset and a block size before the samples
+        let sound_data_size = self.get_audio_size();
+        if sound_data_size + 4 + 26 + 16 + 1 > (<u32>::max_value() as usize) {
+            return Err(PCMError::TooMuchData(sound_data_size));
+        }
+        if self.frames.len() > (<u32>::max_value() as usize) {
+            return Err(PCMError::TooManyFrames(self.frames.len()));
+        }
+        let sound_data_size = sound_data_size as u32;
+        let sound_chunk_size_interior = 8 + sound_data_size;
+        let form_chunk_size_interior =
+            4 + 26 + 8 + sound_chunk_size_interior + (sound_chunk_size_interior & 1);
+        writer.write_all(&[b'F', b'O', b'R', b'M'])?; // FORM Chunk
+        writer.write_be_to_u32(form_chunk_size_interior)?; // Interior size of FORM Chunk
+        writer.write_all(&[b'A', b'I', b'F', b'F'])?; // AIFF Form Type
+        writer.write_all(&[b'C', b'O', b'M', b'M'])?; // Common Chunk
+        writer.write_be_to_u32(18)?; // Common Chunk interior size
+        writer.write_be_to_u16(self.parameters.nb_channels)?; // Number of Channels
+        writer.write_be_to_u32(self.frames.len() as u32)?; // Number of Sample Frames
+        writer.write_be_to_u16(self.parameters.sample_type.get_binary_size())?; // Sample Size
+        writer.write_all(&u32_to_extended(self.parameters.sample_rate))?; // Sample Rate
+        writer.write_all(&[b'S', b'S', b'N', b'D'])?; // Sound Data Chunk
+        writer.write_be_to_u32(sound_chunk_size_interior)?; // Sound Data Chunk interior size
+        writer.write_be_to_u32(0)?; // Offset
+        writer.write_be_to_u32(0)?; // Block Size
+        for frame in &self.frames {
+            for sample in &frame.samples {
+                match sample {
+                    Sample::Unsigned8bits(s) => writer.write_to_i8((i16::from(*s) - 128) as i8)?,
+                    Sample::Signed16bits(s) => writer.write_be_to_i16(*s)?,
+                    Sample::Signed24bits(s) => writer.write_all(&[
+                        (s.value >> 16) as u8,
+                        (s.value >> 8) as u8,
+                        s.value as u8,
+                    ])?,
+                    Sample::Signed32bits(s) => writer.write_be_to_i32(*s)?,
+                    s => return Err(PCMError::UnsupportedSampleType(s.clone())),
+                }
+            }
+        }
+        if sound_chunk_size_interior & 1 == 1 {
+            writer.write_to_u8(0)?; // Padding to keep chunks word-aligned
+        }
+        Ok(())
+    }
+}
+
+impl Sample {
+    /// Returns a Sample from a number of bits per sample found in an AIFF common chunk
+    pub fn aiff_from_bps(bits_per_sample: u16) -> Result<Sample> {
+        Ok(match bits_per_sample {
+            1..=8 => Sample::Unsigned8bits(0),
+            9..=16 => Sample::Signed16bits(0),
+            17..=24 => Sample::Signed24bits(I24 { value: 0 }),
+            25..=32 => Sample::Signed32bits(0),
+            _ => return Err(PCMError::UnknownBitsPerSample(bits_per_sample)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ez_io::WriteE;
+    use std::io::{Cursor, Write};
+    use error::PCMError;
+    use sample_types::I24;
+    use {Frame, PCMParameters, Sample, PCM};
+    /// Mono PCM at 44100 Hz with the given samples
+    fn mono(sample_type: Sample, samples: Vec<Sample>) -> PCM {
+        PCM {
+            frames: samples
+                .into_iter()
+                .map(|s| Frame { samples: vec![s] })
+                .collect(),
+            ..PCM::empty(PCMParameters::new(44100, 1, sample_type).unwrap())
+        }
+    }
+    #[test]
+    fn import_aiff() {
+        let mut aiff = Cursor::new(Vec::new());
+        aiff.write_all(b"FORM").unwrap();
+        aiff.write_be_to_u32(4 + 26 + 8 + 8 + 12).unwrap();
+        aiff.write_all(b"AIFFCOMM").unwrap();
+        aiff.write_be_to_u32(18).unwrap();
+        aiff.write_be_to_u16(2).unwrap();
+        aiff.write_be_to_u32(3).unwrap();
+        aiff.write_be_to_u16(16).unwrap();
+        // 8000 Hz as an 80 bits extended float
+        aiff.write_all(&[0x40, 0x0B, 0xFA, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        aiff.write_all(b"NAME").unwrap();
+        aiff.write_be_to_u32(0).unwrap();
+        aiff.write_all(b"SSND").unwrap();
+        aiff.write_be_to_u32(8 + 12).unwrap();
+        aiff.write_be_to_u32(0).unwrap();
+        aiff.write_be_to_u32(0).unwrap();
+        for value in &[1i16, -1, 256, -256, 32767, -32768] {
+            aiff.write_be_to_i16(*value).unwrap();
+        }
+        aiff.set_position(0);
+        let pcm = PCM::aiff_import_file(&mut aiff).unwrap();
+        assert_eq!(pcm.parameters.sample_rate, 8000);
+        assert_eq!(pcm.parameters.nb_channels, 2);
+        assert_eq!(pcm.frames.len(), 3);
+        match (&pcm.frames[1].samples[0], &pcm.frames[2].samples[1]) {
+            (Sample::Signed16bits(a), Sample::Signed16bits(b)) => {
+                assert_eq!(*a, 256);
+                assert_eq!(*b, -32768);
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn sound_data_offset_past_chunk() {
+        for offset in &[16u32, <u32>::max_value()] {
+            let mut aiff = Cursor::new(Vec::new());
+            aiff.write_all(b"FORM").unwrap();
+            aiff.write_be_to_u32(4 + 26 + 8 + 12).unwrap();
+            aiff.write_all(b"AIFFCOMM").unwrap();
+            aiff.write_be_to_u32(18).unwrap();
+            aiff.write_be_to_u16(1).unwrap();
+            aiff.write_be_to_u32(2).unwrap();
+            aiff.write_be_to_u16(16).unwrap();
+            aiff.write_all(&[0x40, 0x0B, 0xFA, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+            aiff.write_all(b"SSND").unwrap();
+            aiff.write_be_to_u32(8 + 4).unwrap();
+            aiff.write_be_to_u32(*offset).unwrap();
+            aiff.write_be_to_u32(0).unwrap();
+            aiff.write_be_to_i16(1).unwrap();
+            aiff.write_be_to_i16(-1).unwrap();
+            aiff.set_position(0);
+            match PCM::aiff_import_file(&mut aiff) {
+                Err(PCMError::ParseError { chunk, kind, .. }) => match *kind {
+                    PCMError::InvalidHeader(_) => assert_eq!(&chunk, b"SSND"),
+                    _ => panic!(),
+                },
+                _ => panic!(),
+            }
+        }
+    }
+    #[test]
+    fn export_then_import() {
+        let sample_types = [
+            Sample::Unsigned8bits(0),
+            Sample::Signed16bits(0),
+            Sample::Signed24bits(I24 { value: 0 }),
+            Sample::Signed32bits(0),
+        ];
+        for sample_type in &sample_types {
+            let pcm = mono(
+                sample_type.clone(),
+                (0..5)
+                    .map(|i| Sample::from_f64(f64::from(i) / 2f64 - 1f64, sample_type))
+                    .collect(),
+            );
+            let mut aiff = Cursor::new(Vec::new());
+            pcm.aiff_export_file(&mut aiff).unwrap();
+            aiff.set_position(0);
+            let imported = PCM::aiff_import_file(&mut aiff).unwrap();
+            assert_eq!(imported.parameters.sample_rate, 44100);
+            assert_eq!(imported.frames, pcm.frames);
+        }
+    }
+    #[test]
+    fn export_unsupported() {
+        let pcm = mono(Sample::Float(0f32), vec![Sample::Float(0.5)]);
+        let mut aiff = Cursor::new(Vec::new());
+        match pcm.aiff_export_file(&mut aiff) {
+            Err(PCMError::UnsupportedSampleType(Sample::Float(_))) => {}
+            _ => panic!(),
+        }
+        assert!(aiff.into_inner().is_empty());
+    }
+}
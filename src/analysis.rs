@@ -0,0 +1,288 @@
+use {PCMError, Result, Sample, PCM};
+
+impl PCM {
+    /// Returns the Root Mean Square level of all samples, linear between 0 and 1
+    pub fn rms(&self) -> f64 {
+        let nb_samples: usize = self.frames.iter().map(|f| f.samples.len()).sum();
+        if nb_samples == 0 {
+            return 0f64;
+        }
+        let sum: f64 = self
+            .frames
+            .iter()
+            .flat_map(|f| f.samples.iter())
+            .map(|s| s.to_f64().powi(2))
+            .sum();
+        (sum / nb_samples as f64).sqrt()
+    }
+    /// Returns the Root Mean Square level of all samples in dBFS, negative infinity for silence
+    pub fn rms_db(&self) -> f64 {
+        20f64 * self.rms().log10()
+    }
+    /// Returns the biggest absolute value of all samples, linear between 0 and 1
+    pub fn peak(&self) -> f64 {
+        self.frames
+            .iter()
+            .flat_map(|f| f.samples.iter())
+            .map(|s| s.to_f64().abs())
+            .fold(0f64, f64::max)
+    }
+    /// Returns the biggest absolute value of all samples in dBFS, negative infinity for silence
+    pub fn peak_db(&self) -> f64 {
+        20f64 * self.peak().log10()
+    }
+    /// Returns the Root Mean Square level of each channel, linear between 0 and 1
+    pub fn channel_rms(&self) -> Vec<f64> {
+        let mut sums = vec![0f64; self.parameters.nb_channels as usize];
+        for frame in &self.frames {
+            for (sum, sample) in sums.iter_mut().zip(frame.samples.iter()) {
+                *sum += sample.to_f64().powi(2);
+            }
+        }
+        sums.iter()
+            .map(|s| {
+                if self.frames.is_empty() {
+                    0f64
+                } else {
+                    (s / self.frames.len() as f64).sqrt()
+                }
+            })
+            .collect()
+    }
+    /// Returns the biggest absolute value of each channel, linear between 0 and 1
+    pub fn channel_peak(&self) -> Vec<f64> {
+        let mut peaks = vec![0f64; self.parameters.nb_channels as usize];
+        for frame in &self.frames {
+            for (peak, sample) in peaks.iter_mut().zip(frame.samples.iter()) {
+                *peak = peak.max(sample.to_f64().abs());
+            }
+        }
+        peaks
+    }
+    /// Returns for each channel the frame where the biggest absolute value is first reached and the value there,
+    /// normalized between -1 and 1. The frame is 0 in silent channels.
+    pub fn peak_positions(&self) -> Vec<(u16, u64, f64)> {
+        let mut peaks: Vec<(u16, u64, f64)> = (0..self.parameters.nb_channels)
+            .map(|channel| (channel, 0, 0f64))
+            .collect();
+        for (frame_id, frame) in self.frames.iter().enumerate() {
+            for (peak, sample) in peaks.iter_mut().zip(frame.samples.iter()) {
+                let value = sample.to_f64();
+                if value.abs() > peak.2.abs() {
+                    peak.1 = frame_id as u64;
+                    peak.2 = value;
+                }
+            }
+        }
+        peaks
+    }
+    /// Counts the samples at the minimum or maximum value of their type, or beyond 1 for floats.
+    /// Only runs of at least consecutive clipped samples in a channel are counted.
+    pub fn count_clipped_samples(&self, consecutive: usize) -> usize {
+        let mut runs = vec![0usize; self.parameters.nb_channels as usize];
+        let mut count = 0;
+        for frame in &self.frames {
+            for (run, sample) in runs.iter_mut().zip(frame.samples.iter()) {
+                let clipped = if sample.is_float() {
+                    sample.to_f64().abs() >= 1f64
+                } else {
+                    *sample == Sample::max_value(sample) || *sample == Sample::min_value(sample)
+                };
+                if clipped {
+                    *run += 1;
+                } else {
+                    if *run >= consecutive {
+                        count += *run;
+                    }
+                    *run = 0;
+                }
+            }
+        }
+        count + runs.iter().filter(|r| **r >= consecutive).sum::<usize>()
+    }
+    /// Returns how many bits of integer samples are actually used, the lowest bits that are always zero being left out.
+    /// Silence gives 0, float samples count as fully used.
+    pub fn effective_bit_depth(&self) -> u16 {
+        let mut depth = 0;
+        for sample in self.frames.iter().flat_map(|f| f.samples.iter()) {
+            let bits = match sample {
+                Sample::Unsigned8bits(v) => i64::from(*v ^ 0x80),
+                Sample::Signed16bits(v) => i64::from(*v),
+                Sample::Signed24bits(v) => i64::from(v.value),
+                Sample::Signed32bits(v) => i64::from(*v),
+                _ => {
+                    depth = depth.max(sample.get_binary_size());
+                    continue;
+                }
+            };
+            if bits != 0 {
+                depth = depth.max(sample.get_binary_size() - bits.trailing_zeros() as u16);
+            }
+        }
+        depth
+    }
+    /// Splits the signal into bins of consecutive frames and returns the lowest and highest normalized sample of each,
+    /// for drawing a waveform. Bins holding no frames give (0, 0).
+    pub fn waveform_bins(&self, bins: usize) -> Vec<(f64, f64)> {
+        self.min_max_bins(bins, None)
+    }
+    /// Same as waveform_bins, using only the samples of a single channel
+    pub fn waveform_bins_channel(&self, channel: u16, bins: usize) -> Result<Vec<(f64, f64)>> {
+        if channel >= self.parameters.nb_channels {
+            return Err(PCMError::ChannelOutOfRange(channel));
+        }
+        Ok(self.min_max_bins(bins, Some(channel as usize)))
+    }
+    /// Returns the lowest and highest normalized sample of each bin, in all channels or in a single one
+    fn min_max_bins(&self, bins: usize, channel: Option<usize>) -> Vec<(f64, f64)> {
+        (0..bins)
+            .map(|bin| {
+                let start = bin * self.frames.len() / bins;
+                let end = (bin + 1) * self.frames.len() / bins;
+                let values = self.frames[start..end].iter().flat_map(|f| {
+                    f.samples
+                        .iter()
+                        .enumerate()
+                        .filter(|(c, _)| channel.is_none() || channel == Some(*c))
+                        .map(|(_, s)| s.to_f64())
+                });
+                values
+                    .fold(None, |range: Option<(f64, f64)>, v| match range {
+                        Some((min, max)) => Some((min.min(v), max.max(v))),
+                        None => Some((v, v)),
+                    })
+                    .unwrap_or((0f64, 0f64))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sample_types::I24;
+    use {BTreeMap, Frame, PCMParameters, Sample, PCM};
+    #[test]
+    fn levels() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Float(0f32),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..100)
+                .map(|i| Frame {
+                    samples: vec![
+                        Sample::Float(if i % 2 == 0 { 0.5 } else { -0.5 }),
+                        Sample::Float(0f32),
+                    ],
+                })
+                .collect(),
+        };
+        assert_eq!(pcm.peak(), 0.5);
+        assert!((pcm.peak_db() + 6.0206).abs() < 0.001);
+        assert!((pcm.rms() - 0.125f64.sqrt()).abs() < 1e-9);
+        assert_eq!(pcm.channel_rms(), vec![0.5, 0.0]);
+        assert_eq!(pcm.channel_peak(), vec![0.5, 0.0]);
+    }
+    #[test]
+    fn peak_positions() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: [(100, 0), (-16384, 0), (16384, 0)]
+                .iter()
+                .map(|&(left, right)| Frame {
+                    samples: vec![Sample::Signed16bits(left), Sample::Signed16bits(right)],
+                })
+                .collect(),
+        };
+        assert_eq!(pcm.peak_positions(), vec![(0, 1, -0.5), (1, 0, 0.0)]);
+    }
+    #[test]
+    fn waveform_bins() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Float(0f32),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: [(0.25, -0.5), (-0.25, 0.0), (1.0, 0.5), (0.5, 0.0)]
+                .iter()
+                .map(|&(left, right)| Frame {
+                    samples: vec![Sample::Float(left), Sample::Float(right)],
+                })
+                .collect(),
+        };
+        assert_eq!(pcm.waveform_bins(2), vec![(-0.5, 0.25), (0.0, 1.0)]);
+        assert_eq!(
+            pcm.waveform_bins_channel(1, 4).unwrap(),
+            vec![(-0.5, -0.5), (0.0, 0.0), (0.5, 0.5), (0.0, 0.0)]
+        );
+        assert_eq!(pcm.waveform_bins(8)[0], (0.0, 0.0));
+        assert!(pcm.waveform_bins_channel(2, 4).is_err());
+    }
+    #[test]
+    fn count_clipped_samples() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: [32767, 32767, 0, -32768, 32766, 32767, 32767, 32767]
+                .iter()
+                .map(|&v| Frame {
+                    samples: vec![Sample::Signed16bits(v)],
+                })
+                .collect(),
+        };
+        assert_eq!(pcm.count_clipped_samples(1), 6);
+        assert_eq!(pcm.count_clipped_samples(2), 5);
+        assert_eq!(pcm.count_clipped_samples(3), 3);
+        let float = pcm.convert_sample_type(Sample::Float(0f32));
+        assert_eq!(float.count_clipped_samples(1), 1);
+    }
+    #[test]
+    fn effective_bit_depth() {
+        let mut pcm = PCM::empty(PCMParameters::new(8000, 1, Sample::Signed16bits(0)).unwrap());
+        assert_eq!(pcm.effective_bit_depth(), 0);
+        pcm.frames = [0, 0x100, -0x300, 0x7F00]
+            .iter()
+            .map(|&v| Frame {
+                samples: vec![Sample::Signed16bits(v)],
+            })
+            .collect();
+        assert_eq!(pcm.effective_bit_depth(), 8);
+        let upconverted = pcm.convert_sample_type(Sample::Signed24bits(I24 { value: 0 }));
+        assert_eq!(upconverted.effective_bit_depth(), 8);
+        let unsigned = pcm.convert_sample_type(Sample::Unsigned8bits(0));
+        assert_eq!(unsigned.effective_bit_depth(), 8);
+        pcm.frames[0].samples[0] = Sample::Signed16bits(-1);
+        assert_eq!(pcm.effective_bit_depth(), 16);
+        let float = pcm.convert_sample_type(Sample::Float(0f32));
+        assert_eq!(float.effective_bit_depth(), 32);
+    }
+}
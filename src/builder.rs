@@ -0,0 +1,121 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use {BTreeMap, Frame, PCMError, PCMParameters, Result, Sample, PCM};
+
+/// Builds PCM data step by step, frames are checked against the parameters when building
+pub struct PCMBuilder {
+    parameters: PCMParameters,
+    frames: Vec<Frame>,
+    pending_samples: Vec<Sample>,
+}
+
+impl PCMBuilder {
+    /// Creates a builder for mono Signed 16 bits data at 44100 Hz
+    pub fn new() -> PCMBuilder {
+        PCMBuilder {
+            parameters: PCMParameters {
+                sample_rate: 44100,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            frames: Vec::new(),
+            pending_samples: Vec::new(),
+        }
+    }
+    /// Sets the number of samples per second
+    pub fn sample_rate(mut self, sample_rate: u32) -> PCMBuilder {
+        self.parameters.sample_rate = sample_rate;
+        self
+    }
+    /// Sets the number of channels, must be set before pushing interleaved samples
+    pub fn channels(mut self, nb_channels: u16) -> PCMBuilder {
+        self.parameters.nb_channels = nb_channels;
+        self
+    }
+    /// Sets the type of the samples, must be set before pushing interleaved samples
+    pub fn sample_type(mut self, sample_type: Sample) -> PCMBuilder {
+        self.parameters.sample_type = sample_type;
+        self
+    }
+    /// Adds a frame as is
+    pub fn push_frame(mut self, frame: Frame) -> PCMBuilder {
+        self.frames.push(frame);
+        self
+    }
+    /// Adds interleaved samples between -1 and 1, quantized to the sample type
+    pub fn push_interleaved(mut self, samples: &[f64]) -> PCMBuilder {
+        let nb_channels = self.parameters.nb_channels as usize;
+        for value in samples {
+            self.pending_samples
+                .push(Sample::from_f64(*value, &self.parameters.sample_type));
+            if self.pending_samples.len() == nb_channels {
+                self.frames.push(Frame {
+                    samples: self.pending_samples.split_off(0),
+                });
+            }
+        }
+        self
+    }
+    /// Returns the PCM once every frame has been checked
+    pub fn build(self) -> Result<PCM> {
+        if !self.pending_samples.is_empty() {
+            return Err(PCMError::PartialFrame(
+                self.pending_samples.len() * self.parameters.bytes_per_sample() as usize,
+            ));
+        }
+        let pcm = PCM {
+            parameters: self.parameters,
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: self.frames,
+        };
+        pcm.validate()?;
+        Ok(pcm)
+    }
+}
+
+impl Default for PCMBuilder {
+    fn default() -> PCMBuilder {
+        PCMBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PCMBuilder;
+    use error::PCMError;
+    use {Frame, Sample};
+    #[test]
+    fn build_stereo() {
+        let pcm = PCMBuilder::new()
+            .sample_rate(8000)
+            .channels(2)
+            .sample_type(Sample::Signed16bits(0))
+            .push_interleaved(&[0f64, 1f64, -1f64, 0.5])
+            .push_frame(Frame {
+                samples: vec![Sample::Signed16bits(3), Sample::Signed16bits(4)],
+            })
+            .build()
+            .unwrap();
+        assert_eq!(pcm.parameters.sample_rate, 8000);
+        assert_eq!(pcm.frames.len(), 3);
+        match (&pcm.frames[0].samples[1], &pcm.frames[1].samples[0]) {
+            (Sample::Signed16bits(a), Sample::Signed16bits(b)) => {
+                assert_eq!(*a, 32767);
+                assert_eq!(*b, -32768);
+            }
+            _ => panic!(),
+        }
+        match PCMBuilder::new()
+            .channels(2)
+            .push_interleaved(&[0f64, 0f64, 0f64])
+            .build()
+        {
+            Err(PCMError::PartialFrame(2)) => {}
+            _ => panic!(),
+        }
+    }
+}
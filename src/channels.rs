@@ -0,0 +1,276 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use {Frame, PCMError, PCMParameters, Result, Sample, PCM};
+
+impl PCM {
+    /// Iterates over the samples of a single channel, without copying them
+    pub fn channel_samples<'a>(
+        &'a self,
+        channel: u16,
+    ) -> Result<impl Iterator<Item = &'a Sample> + 'a> {
+        if channel >= self.parameters.nb_channels {
+            return Err(PCMError::ChannelOutOfRange(channel));
+        }
+        Ok(self.frames.iter().map(move |f| &f.samples[channel as usize]))
+    }
+    /// Returns the samples with one Vec per channel instead of one per frame
+    pub fn to_planar(&self) -> Vec<Vec<Sample>> {
+        (0..self.parameters.nb_channels as usize)
+            .map(|channel| {
+                self.frames
+                    .iter()
+                    .map(|f| f.samples[channel].clone())
+                    .collect()
+            })
+            .collect()
+    }
+    /// Interleaves one Vec of samples per channel back into frames.
+    /// There must be as many Vecs as channels in the parameters, all of the same length.
+    pub fn from_planar(planar: Vec<Vec<Sample>>, parameters: PCMParameters) -> Result<PCM> {
+        if planar.len() != parameters.nb_channels as usize {
+            return Err(PCMError::IncompatibleParameters("number of channels"));
+        }
+        let nb_frames = planar.first().map_or(0, |c| c.len());
+        if planar.iter().any(|c| c.len() != nb_frames) {
+            return Err(PCMError::IncompatibleParameters("frame count"));
+        }
+        let mut channels: Vec<_> = planar.into_iter().map(|c| c.into_iter()).collect();
+        let frames = (0..nb_frames)
+            .map(|_| Frame {
+                samples: channels.iter_mut().filter_map(|c| c.next()).collect(),
+            })
+            .collect();
+        let pcm = PCM {
+            frames,
+            ..PCM::empty(parameters)
+        };
+        pcm.validate()?;
+        Ok(pcm)
+    }
+    /// Returns a mono PCM containing only one of the channels
+    pub fn extract_channel(&self, index: u16) -> Result<PCM> {
+        if index >= self.parameters.nb_channels {
+            return Err(PCMError::ChannelOutOfRange(index));
+        }
+        Ok(self.mono_channel(index as usize))
+    }
+    /// Iterates over the channels as mono PCMs, each one being extracted only when reached
+    pub fn channels_iter(&self) -> impl Iterator<Item = PCM> + '_ {
+        (0..self.parameters.nb_channels as usize).map(move |c| self.mono_channel(c))
+    }
+    /// Returns a mono PCM of a channel, the index being below the number of channels
+    fn mono_channel(&self, index: usize) -> PCM {
+        let mut parameters = self.parameters.clone();
+        parameters.nb_channels = 1;
+        parameters.channel_mask = None;
+        let frames = self
+            .frames
+            .iter()
+            .map(|f| Frame {
+                samples: vec![f.samples[index].clone()],
+            })
+            .collect();
+        self.with_frames(parameters, frames)
+    }
+    /// Interleaves the channels of several PCMs into a single one, in order.
+    /// All PCMs must share the same sample rate, sample type and frame count.
+    pub fn merge_channels(channels: &[PCM]) -> Result<PCM> {
+        let first = match channels.first() {
+            Some(c) => c,
+            None => return Err(PCMError::IncompatibleParameters("number of PCMs, none given")),
+        };
+        for pcm in channels {
+            if pcm.parameters.sample_rate != first.parameters.sample_rate {
+                return Err(PCMError::IncompatibleParameters("sample rate"));
+            }
+            if !pcm.parameters
+                .sample_type
+                .is_same_type(&first.parameters.sample_type)
+            {
+                return Err(PCMError::IncompatibleParameters("sample type"));
+            }
+            if pcm.frames.len() != first.frames.len() {
+                return Err(PCMError::IncompatibleParameters("frame count"));
+            }
+        }
+        let mut parameters = first.parameters.clone();
+        parameters.nb_channels = channels.iter().map(|c| c.parameters.nb_channels).sum();
+        parameters.channel_mask = None;
+        let frames = (0..first.frames.len())
+            .map(|frame_id| Frame {
+                samples: channels
+                    .iter()
+                    .flat_map(|c| c.frames[frame_id].samples.iter().cloned())
+                    .collect(),
+            })
+            .collect();
+        Ok(first.with_frames(parameters, frames))
+    }
+    /// Returns a mono PCM where each sample is the average of all channels in a frame
+    pub fn downmix_to_mono(&self) -> PCM {
+        if self.parameters.nb_channels == 1 {
+            return self.clone();
+        }
+        let mut parameters = self.parameters.clone();
+        parameters.nb_channels = 1;
+        parameters.channel_mask = None;
+        let frames = self
+            .frames
+            .iter()
+            .map(|f| {
+                let sum: f64 = f.samples.iter().map(|s| s.to_f64()).sum();
+                Frame {
+                    samples: vec![Sample::from_f64(
+                        sum / f.samples.len() as f64,
+                        &f.samples[0],
+                    )],
+                }
+            })
+            .collect();
+        self.with_frames(parameters, frames)
+    }
+    /// Returns a stereo PCM with both channels being a copy of this mono PCM
+    pub fn duplicate_to_stereo(&self) -> Result<PCM> {
+        if self.parameters.nb_channels != 1 {
+            return Err(PCMError::IncompatibleParameters(
+                "number of channels, expected mono",
+            ));
+        }
+        let mut parameters = self.parameters.clone();
+        parameters.nb_channels = 2;
+        parameters.channel_mask = None;
+        let frames = self
+            .frames
+            .iter()
+            .map(|f| Frame {
+                samples: vec![f.samples[0].clone(), f.samples[0].clone()],
+            })
+            .collect();
+        Ok(self.with_frames(parameters, frames))
+    }
+    /// Creates a PCM with other parameters and frames, keeping the loops, cue points and metadata of this one
+    fn with_frames(&self, parameters: PCMParameters, frames: Vec<Frame>) -> PCM {
+        PCM {
+            parameters,
+            loop_info: self.loop_info.clone(),
+            markers: self.markers.clone(),
+            broadcast_info: self.broadcast_info.clone(),
+            tags: self.tags.clone(),
+            frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {BTreeMap, Frame, PCMParameters, Sample, PCM};
+    /// Stereo PCM with increasing values on the left and decreasing ones on the right
+    fn stereo() -> PCM {
+        PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..10i16)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i), Sample::Signed16bits(-i)],
+                })
+                .collect(),
+        }
+    }
+    #[test]
+    fn extract_channel() {
+        let pcm = stereo();
+        let right = pcm.extract_channel(1).unwrap();
+        assert_eq!(right.parameters.nb_channels, 1);
+        assert_eq!(right.frames.len(), 10);
+        match right.frames[3].samples[0] {
+            Sample::Signed16bits(v) => assert_eq!(v, -3),
+            _ => panic!(),
+        }
+        assert!(pcm.extract_channel(2).is_err());
+    }
+    #[test]
+    fn channels_iter() {
+        let pcm = stereo();
+        let channels: Vec<PCM> = pcm.channels_iter().collect();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[1], pcm.extract_channel(1).unwrap());
+        assert_eq!(PCM::merge_channels(&channels).unwrap(), pcm);
+    }
+    #[test]
+    fn channel_samples() {
+        let pcm = stereo();
+        let right: Vec<i16> = pcm.channel_samples(1)
+            .unwrap()
+            .map(|s| match s {
+                Sample::Signed16bits(v) => *v,
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(right, (0..10).map(|i| -i).collect::<Vec<i16>>());
+        assert!(pcm.channel_samples(2).is_err());
+    }
+    #[test]
+    fn planar_round_trip() {
+        let pcm = stereo();
+        let planar = pcm.to_planar();
+        assert_eq!(planar.len(), 2);
+        assert_eq!(planar[1].len(), 10);
+        match planar[1][6] {
+            Sample::Signed16bits(v) => assert_eq!(v, -6),
+            _ => panic!(),
+        }
+        let interleaved = PCM::from_planar(planar.clone(), pcm.parameters.clone()).unwrap();
+        assert_eq!(interleaved.frames, pcm.frames);
+        let mut uneven = planar;
+        uneven[0].pop();
+        assert!(PCM::from_planar(uneven, pcm.parameters).is_err());
+    }
+    #[test]
+    fn merge_channels() {
+        let pcm = stereo();
+        let left = pcm.extract_channel(0).unwrap();
+        let right = pcm.extract_channel(1).unwrap();
+        let merged = PCM::merge_channels(&[right, left.clone()]).unwrap();
+        assert_eq!(merged.parameters.nb_channels, 2);
+        match (&merged.frames[4].samples[0], &merged.frames[4].samples[1]) {
+            (Sample::Signed16bits(r), Sample::Signed16bits(l)) => assert_eq!((*r, *l), (-4, 4)),
+            _ => panic!(),
+        }
+        let mut short = left.clone();
+        short.frames.pop();
+        assert!(PCM::merge_channels(&[left, short]).is_err());
+    }
+    #[test]
+    fn downmix_to_mono() {
+        let mut pcm = stereo();
+        pcm.frames[0].samples = vec![Sample::Signed16bits(100), Sample::Signed16bits(300)];
+        let mono = pcm.downmix_to_mono();
+        assert_eq!(mono.parameters.nb_channels, 1);
+        match (&mono.frames[0].samples[0], &mono.frames[5].samples[0]) {
+            (Sample::Signed16bits(first), Sample::Signed16bits(v)) => {
+                assert_eq!((*first, *v), (200, 0))
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn duplicate_to_stereo() {
+        let pcm = stereo();
+        assert!(pcm.duplicate_to_stereo().is_err());
+        let stereo = pcm.extract_channel(1).unwrap().duplicate_to_stereo().unwrap();
+        assert_eq!(stereo.parameters.nb_channels, 2);
+        assert_eq!(stereo.frames[0].get_audio_size(), 4);
+        match (&stereo.frames[7].samples[0], &stereo.frames[7].samples[1]) {
+            (Sample::Signed16bits(l), Sample::Signed16bits(r)) => assert_eq!((*l, *r), (-7, -7)),
+            _ => panic!(),
+        }
+    }
+}
@@ -1,19 +1,470 @@
-use Sample;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use sample_types::I24;
+use core::convert::TryFrom;
+use dither::{DitherType, Ditherer};
+use {BTreeMap, Frame, PCMError, PCMParameters, Sample, PCM};
+
+/// Biggest value a 24 bits signed sample can hold
+const I24_MAX: i32 = 8_388_607;
 
 impl Sample {
     /// Converts a Sample into a Double-Precision Float Sample
     pub fn to_double_float(&self) -> Sample {
+        Sample::DoubleFloat(self.to_f64())
+    }
+    /// Returns the value of a Sample as a Double-Precision Float, between -1 and 1 for integer types.
+    /// Integers are divided by the magnitude of their minimum value for both signs, so the minimum gives exactly -1 and the maximum a bit less than 1.
+    /// Unsigned 8 bits samples are centered on 128, which gives exactly 0.
+    /// ADPCM and G.711 samples are decoded as Signed 16 bits on import and never hold a value, so they return 0.
+    pub fn to_f64(&self) -> f64 {
         match self {
-            Sample::Unsigned8bits(v) => {
-                Sample::DoubleFloat(((f64::from(v.clone())*2f64)/f64::from(<u8>::max_value()))-1f64)
+            Sample::Unsigned8bits(v) => (f64::from(*v) - 128f64) / self.full_scale(),
+            Sample::Signed16bits(v) => f64::from(*v) / self.full_scale(),
+            Sample::Signed24bits(v) => f64::from(v.value) / self.full_scale(),
+            Sample::Signed32bits(v) => f64::from(*v) / self.full_scale(),
+            Sample::Float(v) => f64::from(*v),
+            Sample::DoubleFloat(v) => *v,
+            Sample::ImaADPCM(_)
+            | Sample::MicrosoftADPCM(_)
+            | Sample::ALaw(_)
+            | Sample::MuLaw(_) => 0f64,
+        }
+    }
+    /// Quantizes a Double-Precision Float value into a Sample of the same type as target.
+    /// Values outside of -1 and 1 are clamped for integer types, 1 giving the maximum value, and NaN gives silence. ADPCM and G.711 targets give Signed 16 bits samples, as they are once decoded.
+    pub fn from_f64(value: f64, target: &Sample) -> Sample {
+        let clamped = if value.is_nan() {
+            0f64
+        } else {
+            value.clamp(-1f64, 1f64)
+        };
+        match target {
+            Sample::Unsigned8bits(_) => {
+                target.clamp_to_variant_range(clamped * target.full_scale() + 128f64)
+            }
+            Sample::Float(_) => Sample::Float(value as f32),
+            Sample::DoubleFloat(_) => Sample::DoubleFloat(value),
+            _ => target.clamp_to_variant_range(clamped * target.full_scale()),
+        }
+    }
+    /// Returns silence in the type of variant, which is 128 for Unsigned 8 bits and 0 for other types
+    pub fn zero(variant: &Sample) -> Sample {
+        Sample::from_f64(0f64, variant)
+    }
+    /// Returns the minimum value of the type of variant, -1 for float types
+    pub fn min_value(variant: &Sample) -> Sample {
+        Sample::from_f64(-1f64, variant)
+    }
+    /// Returns the maximum value of the type of variant, 1 for float types
+    pub fn max_value(variant: &Sample) -> Sample {
+        Sample::from_f64(1f64, variant)
+    }
+    /// Returns the magnitude of the minimum value of integer types, which is what they are divided by when normalized.
+    /// Float types are already normalized and return 1.
+    fn full_scale(&self) -> f64 {
+        match self {
+            Sample::Unsigned8bits(_) => 128f64,
+            Sample::Signed16bits(_)
+            | Sample::ImaADPCM(_)
+            | Sample::MicrosoftADPCM(_)
+            | Sample::ALaw(_)
+            | Sample::MuLaw(_) => 32_768f64,
+            Sample::Signed24bits(_) => 8_388_608f64,
+            Sample::Signed32bits(_) => 2_147_483_648f64,
+            Sample::Float(_) | Sample::DoubleFloat(_) => 1f64,
+        }
+    }
+    /// Returns a Sample of the same type as this one holding a raw value, rounded and clamped to the range of integer types.
+    /// ADPCM and G.711 samples give Signed 16 bits samples, as they are once decoded.
+    pub fn clamp_to_variant_range(&self, value: f64) -> Sample {
+        let clamp = |min: f64, max: f64| round(value).max(min).min(max);
+        match self {
+            Sample::Unsigned8bits(_) => Sample::Unsigned8bits(clamp(0f64, 255f64) as u8),
+            Sample::Signed16bits(_)
+            | Sample::ImaADPCM(_)
+            | Sample::MicrosoftADPCM(_)
+            | Sample::ALaw(_)
+            | Sample::MuLaw(_) => {
+                Sample::Signed16bits(
+                    clamp(f64::from(<i16>::min_value()), f64::from(<i16>::max_value())) as i16,
+                )
+            }
+            Sample::Signed24bits(_) => Sample::Signed24bits(I24 {
+                value: clamp(f64::from(-I24_MAX - 1), f64::from(I24_MAX)) as i32,
+            }),
+            Sample::Signed32bits(_) => Sample::Signed32bits(
+                clamp(f64::from(<i32>::min_value()), f64::from(<i32>::max_value())) as i32,
+            ),
+            Sample::Float(_) => Sample::Float(value as f32),
+            Sample::DoubleFloat(_) => Sample::DoubleFloat(value),
+        }
+    }
+    /// Adds two Samples of the same type, saturating at the limits of integer types instead of wrapping around.
+    /// Unsigned 8 bits samples are centered on 128, which is treated as zero.
+    pub fn saturating_add(&self, other: &Sample) -> Result<Sample, PCMError> {
+        Ok(match (self, other) {
+            (Sample::Unsigned8bits(a), Sample::Unsigned8bits(b)) => {
+                self.clamp_to_variant_range(f64::from(*a) + f64::from(*b) - 128f64)
+            }
+            (Sample::Signed16bits(a), Sample::Signed16bits(b)) => {
+                Sample::Signed16bits(a.saturating_add(*b))
+            }
+            (Sample::Signed24bits(a), Sample::Signed24bits(b)) => {
+                self.clamp_to_variant_range(f64::from(a.value) + f64::from(b.value))
+            }
+            (Sample::Signed32bits(a), Sample::Signed32bits(b)) => {
+                Sample::Signed32bits(a.saturating_add(*b))
+            }
+            (Sample::Float(a), Sample::Float(b)) => Sample::Float(a + b),
+            (Sample::DoubleFloat(a), Sample::DoubleFloat(b)) => Sample::DoubleFloat(a + b),
+            (Sample::ImaADPCM(_), _)
+            | (Sample::MicrosoftADPCM(_), _)
+            | (Sample::ALaw(_), _)
+            | (Sample::MuLaw(_), _) => {
+                return Err(PCMError::UnsupportedSampleType(self.clone()))
+            }
+            _ => return Err(PCMError::IncompatibleParameters("sample type")),
+        })
+    }
+    /// Negates a Sample, the minimum value of integer types saturating to the maximum.
+    /// Unsigned 8 bits samples are reflected around 128. ADPCM and G.711 samples never hold a value and are returned as is.
+    pub fn saturating_neg(&self) -> Sample {
+        match self {
+            Sample::Unsigned8bits(v) => self.clamp_to_variant_range(256f64 - f64::from(*v)),
+            Sample::Signed16bits(v) => Sample::Signed16bits(v.saturating_neg()),
+            Sample::Signed24bits(v) => Sample::Signed24bits(I24 {
+                value: (-v.value).min(I24_MAX),
+            }),
+            Sample::Signed32bits(v) => Sample::Signed32bits(v.saturating_neg()),
+            Sample::Float(v) => Sample::Float(-v),
+            Sample::DoubleFloat(v) => Sample::DoubleFloat(-v),
+            Sample::ImaADPCM(_)
+            | Sample::MicrosoftADPCM(_)
+            | Sample::ALaw(_)
+            | Sample::MuLaw(_) => self.clone(),
+        }
+    }
+}
+
+/// Implements exact conversions between a Sample variant and the value it holds.
+/// Getting a value out of a Sample of another variant fails instead of converting, use to_f64 and from_f64 for that.
+macro_rules! sample_value_conversions {
+    ($value_type:ty, $variant:ident) => {
+        impl From<$value_type> for Sample {
+            fn from(value: $value_type) -> Sample {
+                Sample::$variant(value)
+            }
+        }
+        impl TryFrom<Sample> for $value_type {
+            type Error = PCMError;
+            fn try_from(sample: Sample) -> Result<$value_type, PCMError> {
+                match sample {
+                    Sample::$variant(v) => Ok(v),
+                    s => Err(PCMError::UnsupportedSampleType(s)),
+                }
+            }
+        }
+    };
+}
+
+sample_value_conversions!(u8, Unsigned8bits);
+sample_value_conversions!(i16, Signed16bits);
+sample_value_conversions!(I24, Signed24bits);
+sample_value_conversions!(i32, Signed32bits);
+sample_value_conversions!(f32, Float);
+sample_value_conversions!(f64, DoubleFloat);
+
+/// Signed 8 bits values are stored as Unsigned 8 bits Samples, offset by 128
+impl From<i8> for Sample {
+    fn from(value: i8) -> Sample {
+        Sample::Unsigned8bits((value as u8) ^ 0x80)
+    }
+}
+
+/// Reads an Unsigned 8 bits Sample as a signed value centered on 0
+impl TryFrom<Sample> for i8 {
+    type Error = PCMError;
+    fn try_from(sample: Sample) -> Result<i8, PCMError> {
+        match sample {
+            Sample::Unsigned8bits(v) => Ok((v ^ 0x80) as i8),
+            s => Err(PCMError::UnsupportedSampleType(s)),
+        }
+    }
+}
+
+impl PCM {
+    /// Returns a copy of this PCM with all samples converted to the type of target
+    pub fn convert_sample_type(&self, target: Sample) -> PCM {
+        self.convert(target, DitherType::None)
+    }
+    /// Converts all samples to the type of target, does nothing if they already are of this type whatever the value of target
+    pub fn ensure_sample_type(&mut self, target: Sample) {
+        if !self.parameters.sample_type.is_same_type(&target) {
+            *self = self.convert_sample_type(target);
+        }
+    }
+    /// Same as convert_sample_type, but adds Triangular (TPDF) dither when reducing the bit depth to hide quantization distortion
+    pub fn convert_sample_type_dithered(&self, target: Sample) -> PCM {
+        self.convert_sample_type_with_dither(target, DitherType::Triangular)
+    }
+    /// Same as convert_sample_type, but adds a type of dither when reducing the bit depth
+    pub fn convert_sample_type_with_dither(&self, target: Sample, dither: DitherType) -> PCM {
+        if target.get_binary_size() < self.parameters.sample_type.get_binary_size() {
+            self.convert(target, dither)
+        } else {
+            self.convert(target, DitherType::None)
+        }
+    }
+    /// Iterates over all samples as interleaved normalized values
+    pub fn iter_f64(&self) -> impl Iterator<Item = f64> + '_ {
+        self.frames
+            .iter()
+            .flat_map(|f| f.samples.iter().map(Sample::to_f64))
+    }
+    /// Builds a PCM from interleaved normalized values, converted to the sample type of the parameters
+    pub fn from_iter_f64<I: IntoIterator<Item = f64>>(
+        parameters: PCMParameters,
+        values: I,
+    ) -> Result<PCM, PCMError> {
+        let nb_channels = parameters.nb_channels as usize;
+        let mut frames = Vec::new();
+        let mut samples = Vec::with_capacity(nb_channels);
+        for value in values {
+            samples.push(Sample::from_f64(value, &parameters.sample_type));
+            if samples.len() == nb_channels {
+                frames.push(Frame { samples });
+                samples = Vec::with_capacity(nb_channels);
             }
-            Sample::Signed16bits(v) => {
-                Sample::DoubleFloat(f64::from(v.clone())/f64::from(<i16>::max_value()))
+        }
+        if !samples.is_empty() {
+            return Err(PCMError::PartialFrame(
+                samples.len() * parameters.bytes_per_sample() as usize,
+            ));
+        }
+        Ok(PCM {
+            parameters,
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames,
+        })
+    }
+    /// Converts every sample through normalized values, with a type of dither
+    fn convert(&self, target: Sample, dither: DitherType) -> PCM {
+        // Size of the smallest step of the target type, in normalized values
+        let step = if target.is_float() {
+            0f64
+        } else {
+            1f64 / target.full_scale()
+        };
+        let mut ditherer = Ditherer::new(dither, step, self.parameters.nb_channels);
+        let mut parameters = self.parameters.clone();
+        parameters.sample_type = target.clone();
+        PCM {
+            parameters,
+            loop_info: self.loop_info.clone(),
+            markers: self.markers.clone(),
+            broadcast_info: self.broadcast_info.clone(),
+            tags: self.tags.clone(),
+            frames: self
+                .frames
+                .iter()
+                .map(|f| Frame {
+                    samples: f
+                        .samples
+                        .iter()
+                        .enumerate()
+                        .map(|(channel, s)| ditherer.quantize(s.to_f64(), channel, &target))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Rounds half away from zero
+#[cfg(feature = "std")]
+fn round(value: f64) -> f64 {
+    value.round()
+}
+
+/// Rounds half away from zero, f64::round not being available without std
+#[cfg(not(feature = "std"))]
+fn round(value: f64) -> f64 {
+    // Values this big are already whole, NaN and infinities are kept as is
+    if !(value.abs() < 4_503_599_627_370_496f64) {
+        return value;
+    }
+    let truncated = value as i64 as f64;
+    let fraction = value - truncated;
+    if fraction >= 0.5 {
+        truncated + 1f64
+    } else if fraction <= -0.5 {
+        truncated - 1f64
+    } else {
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::I24_MAX;
+    use sample_types::I24;
+    use std::convert::TryFrom;
+    use {BTreeMap, Frame, PCMParameters, Sample, PCM};
+    #[test]
+    fn f64_round_trip() {
+        let samples = [
+            Sample::Unsigned8bits(200),
+            Sample::Signed16bits(-1234),
+            Sample::Signed24bits(I24 { value: 123_456 }),
+            Sample::Signed32bits(-12_345_678),
+            Sample::Float(0.25),
+            Sample::DoubleFloat(-0.75),
+        ];
+        for sample in &samples {
+            let converted = Sample::from_f64(sample.to_f64(), sample);
+            assert_eq!(&converted, sample);
+        }
+        match Sample::from_f64(2f64, &Sample::Signed16bits(0)) {
+            Sample::Signed16bits(v) => assert_eq!(v, <i16>::max_value()),
+            _ => panic!(),
+        }
+        assert_eq!(
+            Sample::from_f64(f64::NAN, &Sample::Signed16bits(0)),
+            Sample::Signed16bits(0)
+        );
+        assert_eq!(
+            Sample::from_f64(f64::NAN, &Sample::Unsigned8bits(0)),
+            Sample::Unsigned8bits(128)
+        );
+    }
+    #[test]
+    fn iter_f64_round_trip() {
+        let parameters = PCMParameters {
+            sample_rate: 8000,
+            nb_channels: 2,
+            sample_type: Sample::Signed16bits(0),
+            channel_mask: None,
+        };
+        let pcm = PCM::from_iter_f64(parameters.clone(), vec![0.5, -0.5, 0f64, -1f64]).unwrap();
+        assert_eq!(pcm.frames.len(), 2);
+        assert_eq!(pcm.frames[1].samples[1], Sample::Signed16bits(-32768));
+        assert_eq!(pcm.iter_f64().collect::<Vec<_>>(), vec![0.5, -0.5, 0f64, -1f64]);
+        assert!(PCM::from_iter_f64(parameters, vec![0f64; 3]).is_err());
+    }
+    #[test]
+    fn ensure_sample_type() {
+        let parameters = PCMParameters::new(8000, 1, Sample::Signed16bits(0)).unwrap();
+        let mut pcm = PCM::from_iter_f64(parameters, vec![0.5, -0.25]).unwrap();
+        let original = pcm.clone();
+        pcm.ensure_sample_type(Sample::Signed16bits(1234));
+        assert_eq!(pcm, original);
+        pcm.ensure_sample_type(Sample::Float(0f32));
+        assert!(pcm.parameters.sample_type.is_same_type(&Sample::Float(0f32)));
+        assert_eq!(pcm.frames[1].samples[0], Sample::Float(-0.25));
+    }
+    #[test]
+    fn zero_min_max() {
+        let unsigned = Sample::Unsigned8bits(42);
+        assert_eq!(Sample::zero(&unsigned), Sample::Unsigned8bits(128));
+        assert_eq!(Sample::min_value(&unsigned), Sample::Unsigned8bits(0));
+        assert_eq!(Sample::max_value(&unsigned), Sample::Unsigned8bits(255));
+        let signed = Sample::Signed24bits(I24 { value: 42 });
+        assert_eq!(Sample::zero(&signed), Sample::Signed24bits(I24 { value: 0 }));
+        assert_eq!(Sample::min_value(&signed), Sample::Signed24bits(I24 { value: -8_388_608 }));
+        assert_eq!(Sample::max_value(&signed), Sample::Signed24bits(I24 { value: I24_MAX }));
+        assert_eq!(Sample::min_value(&Sample::Float(0.5)), Sample::Float(-1f32));
+    }
+    #[test]
+    fn normalization_range() {
+        assert_eq!(Sample::Signed16bits(<i16>::min_value()).to_f64(), -1f64);
+        assert!(Sample::Signed16bits(<i16>::max_value()).to_f64() < 1f64);
+        assert_eq!(Sample::Signed24bits(I24 { value: -8_388_608 }).to_f64(), -1f64);
+        assert_eq!(Sample::Signed32bits(<i32>::min_value()).to_f64(), -1f64);
+        assert!(Sample::Signed32bits(<i32>::max_value()).to_f64() < 1f64);
+        assert_eq!(
+            Sample::from_f64(-1f64, &Sample::Signed32bits(0)),
+            Sample::Signed32bits(<i32>::min_value())
+        );
+    }
+    #[test]
+    fn value_conversions() {
+        assert_eq!(Sample::from(-5i16), Sample::Signed16bits(-5));
+        assert_eq!(i16::try_from(Sample::Signed16bits(-5)).unwrap(), -5);
+        assert_eq!(f64::try_from(Sample::DoubleFloat(0.25)).unwrap(), 0.25);
+        assert!(i16::try_from(Sample::Signed32bits(-5)).is_err());
+        assert!(u8::try_from(Sample::Float(0f32)).is_err());
+        assert_eq!(Sample::from(0i8), Sample::Unsigned8bits(128));
+        assert_eq!(Sample::from(-128i8), Sample::Unsigned8bits(0));
+        assert_eq!(i8::try_from(Sample::Unsigned8bits(255)).unwrap(), 127);
+        assert!(i8::try_from(Sample::Signed16bits(0)).is_err());
+    }
+    #[test]
+    fn unsigned_8bits_centering() {
+        assert_eq!(Sample::Unsigned8bits(128).to_f64(), 0f64);
+        assert_eq!(Sample::Unsigned8bits(0).to_f64(), -1f64);
+        assert_eq!(
+            Sample::from_f64(0f64, &Sample::Unsigned8bits(0)),
+            Sample::Unsigned8bits(128)
+        );
+        assert_eq!(
+            Sample::from_f64(1f64, &Sample::Unsigned8bits(0)),
+            Sample::Unsigned8bits(255)
+        );
+    }
+    #[test]
+    fn saturating_add() {
+        let sum = Sample::Signed16bits(30000).saturating_add(&Sample::Signed16bits(10000));
+        assert_eq!(sum.unwrap(), Sample::Signed16bits(<i16>::max_value()));
+        let sum = Sample::Unsigned8bits(100).saturating_add(&Sample::Unsigned8bits(20));
+        assert_eq!(sum.unwrap(), Sample::Unsigned8bits(0));
+        let sum = Sample::Signed24bits(I24 { value: -8_000_000 })
+            .saturating_add(&Sample::Signed24bits(I24 { value: -1_000_000 }));
+        assert_eq!(sum.unwrap(), Sample::Signed24bits(I24 { value: -8_388_608 }));
+        assert!(Sample::Signed16bits(0).saturating_add(&Sample::Float(0f32)).is_err());
+        assert_eq!(
+            Sample::Signed16bits(0).clamp_to_variant_range(-1e9),
+            Sample::Signed16bits(<i16>::min_value())
+        );
+    }
+    #[test]
+    fn convert_8_to_16_and_back() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Unsigned8bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..=255u8)
+                .map(|i| Frame {
+                    samples: vec![Sample::Unsigned8bits(i)],
+                })
+                .collect(),
+        };
+        let converted = pcm.convert_sample_type(Sample::Signed16bits(0));
+        let back = converted.convert_sample_type(Sample::Unsigned8bits(0));
+        for (original, back) in pcm.frames.iter().zip(back.frames.iter()) {
+            match (&original.samples[0], &back.samples[0]) {
+                (Sample::Unsigned8bits(o), Sample::Unsigned8bits(b)) => assert_eq!(o, b),
+                _ => panic!(),
             }
-            Sample::Signed32bits(v) => {
-                Sample::DoubleFloat(f64::from(v.clone())/f64::from(<i32>::max_value()))
+        }
+        let dithered = converted.convert_sample_type_dithered(Sample::Unsigned8bits(0));
+        for (original, dithered) in pcm.frames.iter().zip(dithered.frames.iter()) {
+            match (&original.samples[0], &dithered.samples[0]) {
+                (Sample::Unsigned8bits(o), Sample::Unsigned8bits(d)) => {
+                    assert!((i16::from(*o) - i16::from(*d)).abs() <= 1)
+                }
+                _ => panic!(),
             }
-            _ => unimplemented!("No conversion to Double Float for this type")
         }
     }
-}
\ No newline at end of file
+}
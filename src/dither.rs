@@ -0,0 +1,123 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use Sample;
+
+/// Noise added to samples when reducing their bit depth, hiding the distortion caused by rounding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DitherType {
+    /// Samples are only rounded
+    None,
+    /// Uniform noise of one step peak to peak
+    Rectangular,
+    /// Sum of two uniform noises (TPDF), two steps peak to peak, which also keeps the noise level independent of the signal
+    Triangular,
+    /// Triangular noise with first-order error feedback, moving the rounding noise towards high frequencies
+    NoiseShaped,
+}
+
+/// Rounds normalized values to a sample type with dither, keeping the rounding error of each channel for noise shaping
+pub(crate) struct Ditherer {
+    dither_type: DitherType,
+    step: f64,
+    noise: NoiseGenerator,
+    errors: Vec<f64>,
+}
+
+impl Ditherer {
+    /// Creates a Ditherer for a number of channels, step being the size of the smallest step of the target type in normalized values
+    pub(crate) fn new(dither_type: DitherType, step: f64, nb_channels: u16) -> Ditherer {
+        Ditherer {
+            dither_type,
+            step,
+            noise: NoiseGenerator::new(),
+            errors: vec![0f64; nb_channels as usize],
+        }
+    }
+    /// Converts a normalized value of a channel to the type of target
+    pub(crate) fn quantize(&mut self, value: f64, channel: usize, target: &Sample) -> Sample {
+        let step = self.step;
+        match self.dither_type {
+            DitherType::None => Sample::from_f64(value, target),
+            DitherType::Rectangular => {
+                Sample::from_f64(value + (self.noise.next_value() - 0.5) * step, target)
+            }
+            DitherType::Triangular => Sample::from_f64(value + self.triangular() * step, target),
+            DitherType::NoiseShaped => {
+                let shaped = value - self.errors.get(channel).cloned().unwrap_or(0f64);
+                let sample = Sample::from_f64(shaped + self.triangular() * step, target);
+                if let Some(error) = self.errors.get_mut(channel) {
+                    // Clipping gives errors too big to be fed back
+                    *error = (sample.to_f64() - shaped).clamp(-2f64 * step, 2f64 * step);
+                }
+                sample
+            }
+        }
+    }
+    /// Returns triangular noise between -1 and 1
+    fn triangular(&mut self) -> f64 {
+        self.noise.next_value() + self.noise.next_value() - 1f64
+    }
+}
+
+/// Small Xorshift pseudo-random generator used for dithering
+struct NoiseGenerator {
+    state: u64,
+}
+
+impl NoiseGenerator {
+    fn new() -> NoiseGenerator {
+        NoiseGenerator {
+            state: 0x2545_F491_4F6C_DD1D,
+        }
+    }
+    /// Returns a value between 0 and 1
+    fn next_value(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DitherType;
+    use sample_types::I24;
+    use {PCMParameters, Sample, PCM};
+    /// Returns how far the mean of 16 bits samples is from a 24 bits value, and the biggest distance of a sample, in 16 bits steps
+    fn dithered_error(value: f64, dither: DitherType) -> (f64, f64) {
+        let sample_type = Sample::Signed24bits(I24 { value: 0 });
+        let parameters = PCMParameters::new(48000, 2, sample_type).unwrap();
+        let pcm = PCM::from_iter_f64(parameters, vec![value; 20000]).unwrap();
+        let original = pcm.frames[0].samples[0].to_f64() * 32768f64;
+        let converted = pcm.convert_sample_type_with_dither(Sample::Signed16bits(0), dither);
+        let errors: Vec<f64> = converted
+            .iter_f64()
+            .map(|v| v * 32768f64 - original)
+            .collect();
+        let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+        (mean.abs(), errors.iter().fold(0f64, |d, e| d.max(e.abs())))
+    }
+    #[test]
+    fn dither_types() {
+        // About 0.3 step above 100
+        let value = 100.3 / 32768f64;
+        let (mean, distance) = dithered_error(value, DitherType::None);
+        assert!((mean - 0.3).abs() < 0.01 && (distance - 0.3).abs() < 0.01);
+        let (mean, distance) = dithered_error(value, DitherType::Rectangular);
+        assert!(mean < 0.02 && distance <= 1f64);
+        let (mean, distance) = dithered_error(value, DitherType::Triangular);
+        assert!(mean < 0.02 && distance <= 2f64);
+        // The rounding error of a sample is taken back on the next one
+        let (mean, distance) = dithered_error(value, DitherType::NoiseShaped);
+        assert!(mean < 0.001 && distance <= 4f64);
+    }
+    #[test]
+    fn no_dither_when_increasing_depth() {
+        let parameters = PCMParameters::new(8000, 1, Sample::Signed16bits(0)).unwrap();
+        let pcm = PCM::from_iter_f64(parameters, vec![0.25, -0.5, 0.125]).unwrap();
+        let converted =
+            pcm.convert_sample_type_with_dither(Sample::Signed32bits(0), DitherType::Triangular);
+        assert_eq!(converted, pcm.convert_sample_type(Sample::Signed32bits(0)));
+    }
+}
@@ -0,0 +1,648 @@
+use std::cmp::Reverse;
+use std::f64::consts::FRAC_PI_2;
+use std::time::Duration;
+use {BroadcastInfo, CuePoint, Frame, LoopInfo, PCMError, Result, Sample, PCM};
+
+impl PCM {
+    /// Removes the frames at the start and at the end where all channels are quieter than a level in dBFS.
+    /// Silence in the middle is kept. Returns how many frames were removed at the start and at the end.
+    pub fn trim_silence(&mut self, threshold_db: f64) -> (usize, usize) {
+        let threshold = 10f64.powf(threshold_db / 20f64);
+        self.trim_silence_by(|f| f.samples.iter().any(|s| s.to_f64().abs() >= threshold))
+    }
+    /// Same as trim_silence, but with a level compared to the distance of integer samples from silence.
+    /// Float samples are not supported.
+    pub fn trim_silence_abs(&mut self, level: i64) -> Result<(usize, usize)> {
+        self.check_integer_samples()?;
+        Ok(self.trim_silence_by(|f| is_loud_abs(f, level)))
+    }
+    /// Removes the frames at the start and at the end that are not loud
+    fn trim_silence_by<F: Fn(&Frame) -> bool>(&mut self, is_loud: F) -> (usize, usize) {
+        let start = match self.frames.iter().position(&is_loud) {
+            Some(p) => p,
+            None => {
+                // Only silence
+                let nb_frames = self.frames.len();
+                self.frames.clear();
+                self.loop_info = None;
+                self.markers.clear();
+                return (nb_frames, 0);
+            }
+        };
+        let end = self.frames.iter().rposition(&is_loud).unwrap_or(start) + 1;
+        let removed_end = self.frames.len() - end;
+        self.frames.truncate(end);
+        self.frames.drain(..start);
+        self.loop_info = self.clip_loops(start as u64, end as u64);
+        self.markers = self.clip_markers(start as u64, end as u64);
+        self.broadcast_info = self.shifted_broadcast_info(start as u64);
+        (start, removed_end)
+    }
+    /// Splits the signal at every run of frames where all channels are quieter than a level in dBFS for at least min_silence.
+    /// These runs are left out of the segments, shorter silences are kept in them, even at the start or the end of the signal.
+    /// Segments keep the parameters and the cue points they contain, but not the loops.
+    pub fn split_on_silence(&self, threshold_db: f64, min_silence: Duration) -> Vec<PCM> {
+        let threshold = 10f64.powf(threshold_db / 20f64);
+        self.split_on_silence_by(
+            |f| f.samples.iter().any(|s| s.to_f64().abs() >= threshold),
+            min_silence,
+        )
+    }
+    /// Same as split_on_silence, but with a level compared to the distance of integer samples from silence.
+    /// Float samples are not supported.
+    pub fn split_on_silence_abs(&self, level: i64, min_silence: Duration) -> Result<Vec<PCM>> {
+        self.check_integer_samples()?;
+        Ok(self.split_on_silence_by(|f| is_loud_abs(f, level), min_silence))
+    }
+    /// Splits the signal at every run of frames that are not loud for at least min_silence
+    fn split_on_silence_by<F: Fn(&Frame) -> bool>(
+        &self,
+        is_loud: F,
+        min_silence: Duration,
+    ) -> Vec<PCM> {
+        let min_frames = ((min_silence.as_secs_f64() * f64::from(self.parameters.sample_rate))
+            .round() as usize)
+            .max(1);
+        let mut bounds = Vec::new();
+        let mut start = 0;
+        let mut silence_start = None;
+        for (frame_id, frame) in self.frames.iter().enumerate() {
+            if is_loud(frame) {
+                if let Some(silence_start) = silence_start.take() {
+                    if frame_id - silence_start >= min_frames {
+                        bounds.push((start, silence_start));
+                        start = frame_id;
+                    }
+                }
+            } else if silence_start.is_none() {
+                silence_start = Some(frame_id);
+            }
+        }
+        let end = match silence_start {
+            Some(s) if self.frames.len() - s >= min_frames => s,
+            _ => self.frames.len(),
+        };
+        bounds.push((start, end));
+        bounds
+            .into_iter()
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| {
+                let mut segment = self
+                    .slice(start as u64, end as u64)
+                    .expect("segment bounds are inside the signal");
+                segment.loop_info = None;
+                segment
+            })
+            .collect()
+    }
+    /// Returns a new PCM containing the frames from start_frame up to end_frame excluded.
+    /// Loops outside of this range are dropped and the ones overlapping it are shortened.
+    pub fn slice(&self, start_frame: u64, end_frame: u64) -> Result<PCM> {
+        if start_frame > end_frame || end_frame > self.frames.len() as u64 {
+            return Err(PCMError::InvalidFrameRange(start_frame, end_frame));
+        }
+        Ok(PCM {
+            parameters: self.parameters.clone(),
+            loop_info: self.clip_loops(start_frame, end_frame),
+            markers: self.clip_markers(start_frame, end_frame),
+            broadcast_info: self.shifted_broadcast_info(start_frame),
+            tags: self.tags.clone(),
+            frames: self.frames[start_frame as usize..end_frame as usize].to_vec(),
+        })
+    }
+    /// Adds the frames of another PCM at the end of this one, along with its loops and its cue points, renumbered after the highest identifier.
+    /// Both must have the same sample rate, number of channels and sample type.
+    pub fn append(&mut self, other: &PCM) -> Result<()> {
+        self.parameters.check_compatible(&other.parameters)?;
+        if let Some(ref other_loops) = other.loop_info {
+            let offset = self.frames.len() as u64;
+            self.loop_info
+                .get_or_insert_with(Vec::new)
+                .extend(other_loops.iter().map(|l| LoopInfo {
+                    loop_start: l.loop_start + offset,
+                    loop_end: l.loop_end + offset,
+                }));
+        }
+        // Cue point identifiers come from files, the appended ones are renumbered after the highest one
+        let mut next_id = self
+            .markers
+            .iter()
+            .map(|m| m.id)
+            .max()
+            .map_or(0, |id| id.wrapping_add(1));
+        let position_offset = self.frames.len() as u64;
+        for marker in &other.markers {
+            self.markers.push(CuePoint {
+                id: next_id,
+                position: marker.position + position_offset,
+                label: marker.label.clone(),
+            });
+            next_id = next_id.wrapping_add(1);
+        }
+        self.frames.extend_from_slice(&other.frames);
+        Ok(())
+    }
+    /// Joins another PCM after this one, its start overlapping the end of this one with an equal-power crossfade.
+    /// Both must have the same sample rate, number of channels and sample type, and last at least as long as the overlap.
+    /// Loops and cue points are kept as in append, those of the other PCM being moved back by the overlap.
+    pub fn crossfade(&self, other: &PCM, overlap: Duration) -> Result<PCM> {
+        self.parameters.check_compatible(&other.parameters)?;
+        let nb_frames =
+            (overlap.as_secs_f64() * f64::from(self.parameters.sample_rate)).round() as usize;
+        if nb_frames > self.frames.len() || nb_frames > other.frames.len() {
+            return Err(PCMError::InvalidFrameRange(0, nb_frames as u64));
+        }
+        let fade_start = self.frames.len() - nb_frames;
+        let mut joined = self.clone();
+        joined.frames.truncate(fade_start);
+        joined.append(other)?;
+        let fading = self.frames[fade_start..]
+            .iter()
+            .zip(joined.frames[fade_start..].iter_mut());
+        for (frame_id, (out, frame)) in fading.enumerate() {
+            let angle = frame_id as f64 / nb_frames as f64 * FRAC_PI_2;
+            for (out, sample) in out.samples.iter().zip(frame.samples.iter_mut()) {
+                let value = out.to_f64() * angle.cos() + sample.to_f64() * angle.sin();
+                *sample = Sample::from_f64(value, sample);
+            }
+        }
+        Ok(joined)
+    }
+    /// Adds silent frames at the end, silence being 128 for Unsigned 8 bits samples and 0 for other types
+    pub fn pad_frames(&mut self, count: u64) {
+        let silence = Frame {
+            samples: vec![
+                Sample::zero(&self.parameters.sample_type);
+                self.parameters.nb_channels as usize
+            ],
+        };
+        let nb_frames = self.frames.len() + count as usize;
+        self.frames.resize(nb_frames, silence);
+    }
+    /// Adds silent frames at the end until the signal lasts for a duration, does nothing if it is already longer
+    pub fn pad_to_duration(&mut self, duration: Duration) {
+        let target = (duration.as_secs_f64() * f64::from(self.parameters.sample_rate)).round() as u64;
+        self.pad_frames(target.saturating_sub(self.frames.len() as u64));
+    }
+    /// Removes the frames past a duration, rounded to the nearest frame, does nothing if the signal is already shorter.
+    /// Loops and cue points past the end are dropped, loops going past it are shortened.
+    pub fn truncate_to_duration(&mut self, duration: Duration) {
+        let target = (duration.as_secs_f64() * f64::from(self.parameters.sample_rate)).round() as u64;
+        if target >= self.frames.len() as u64 {
+            return;
+        }
+        self.frames.truncate(target as usize);
+        self.loop_info = self.clip_loops(0, target);
+        self.markers = self.clip_markers(0, target);
+    }
+    /// Sums several PCMs sample by sample, keeping the loops and cue points of the first one.
+    /// All PCMs must share the same parameters and frame count.
+    /// When clamp is false, the sum is scaled down to avoid clipping if it goes past full scale.
+    pub fn mix(inputs: &[PCM], clamp: bool) -> Result<PCM> {
+        let first = match inputs.first() {
+            Some(i) => i,
+            None => return Err(PCMError::IncompatibleParameters("number of PCMs, none given")),
+        };
+        for input in inputs {
+            first.parameters.check_compatible(&input.parameters)?;
+            if input.frames.len() != first.frames.len() {
+                return Err(PCMError::IncompatibleParameters("frame count"));
+            }
+        }
+        let sums: Vec<Vec<f64>> = (0..first.frames.len())
+            .map(|frame_id| {
+                (0..first.parameters.nb_channels as usize)
+                    .map(|channel| {
+                        inputs
+                            .iter()
+                            .map(|i| i.frames[frame_id].samples[channel].to_f64())
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        let peak = sums
+            .iter()
+            .flat_map(|f| f.iter())
+            .fold(0f64, |peak, s| peak.max(s.abs()));
+        let factor = if !clamp && peak > 1f64 { 1f64 / peak } else { 1f64 };
+        let mut mixed = first.clone();
+        for (frame, sums) in mixed.frames.iter_mut().zip(sums.iter()) {
+            for (sample, sum) in frame.samples.iter_mut().zip(sums.iter()) {
+                *sample = Sample::from_f64(sum * factor, sample);
+            }
+        }
+        Ok(mixed)
+    }
+    /// Reverses the order of the frames, loops and cue points are mirrored to stay on the same frames
+    pub fn reverse(&mut self) {
+        self.frames.reverse();
+        let last_frame = (self.frames.len() as u64).saturating_sub(1);
+        if let Some(ref mut loops) = self.loop_info {
+            for l in loops.iter_mut() {
+                let loop_start = last_frame.saturating_sub(l.loop_end);
+                l.loop_end = last_frame.saturating_sub(l.loop_start);
+                l.loop_start = loop_start;
+            }
+        }
+        for marker in &mut self.markers {
+            marker.position = last_frame.saturating_sub(marker.position);
+        }
+    }
+    /// Returns a PCM without loops where playback jumps back to the start of each loop repeats times when reaching its end,
+    /// so a loop is played repeats + 1 times before going on. Loops ending past the last frame are ignored.
+    /// When several loops end on the same frame, the one starting last jumps first.
+    /// Jumping back to a frame resets the loops starting after it, so a loop inside another one is repeated on every pass of the outer one,
+    /// while loops that only partly overlap are each repeated once in total.
+    /// Cue points are kept at the first time their frame is played.
+    pub fn render_loops(&self, repeats: u32) -> PCM {
+        let mut loops: Vec<&LoopInfo> = self
+            .loop_info
+            .iter()
+            .flat_map(|l| l.iter())
+            .filter(|l| l.loop_start <= l.loop_end && l.loop_end < self.frames.len() as u64)
+            .collect();
+        loops.sort_by_key(|l| Reverse(l.loop_start));
+        let mut remaining = vec![repeats; loops.len()];
+        // Ranges of frames played in order
+        let mut runs = Vec::new();
+        let mut cursor = 0u64;
+        while cursor < self.frames.len() as u64 {
+            let next_end = loops
+                .iter()
+                .zip(remaining.iter())
+                .filter(|(l, r)| **r > 0 && l.loop_end >= cursor)
+                .map(|(l, _)| l.loop_end)
+                .min();
+            let next_end = match next_end {
+                Some(e) => e,
+                None => {
+                    runs.push((cursor, self.frames.len() as u64));
+                    break;
+                }
+            };
+            runs.push((cursor, next_end + 1));
+            let jump = loops
+                .iter()
+                .zip(remaining.iter())
+                .position(|(l, r)| *r > 0 && l.loop_end == next_end)
+                .expect("a loop ends on this frame");
+            remaining[jump] -= 1;
+            cursor = loops[jump].loop_start;
+            for (l, r) in loops.iter().zip(remaining.iter_mut()) {
+                if l.loop_start > cursor {
+                    *r = repeats;
+                }
+            }
+        }
+        let mut markers = Vec::with_capacity(self.markers.len());
+        for marker in &self.markers {
+            let mut offset = 0;
+            for &(start, end) in &runs {
+                if marker.position >= start && marker.position < end {
+                    markers.push(CuePoint {
+                        position: offset + marker.position - start,
+                        ..marker.clone()
+                    });
+                    break;
+                }
+                offset += end - start;
+            }
+        }
+        PCM {
+            parameters: self.parameters.clone(),
+            loop_info: None,
+            markers,
+            broadcast_info: self.broadcast_info.clone(),
+            tags: self.tags.clone(),
+            frames: runs
+                .iter()
+                .flat_map(|&(start, end)| self.frames[start as usize..end as usize].iter().cloned())
+                .collect(),
+        }
+    }
+    /// Returns an error if samples are not integers
+    fn check_integer_samples(&self) -> Result<()> {
+        if self.parameters.sample_type.is_float() {
+            return Err(PCMError::UnsupportedSampleType(
+                self.parameters.sample_type.clone(),
+            ));
+        }
+        Ok(())
+    }
+    /// Returns the loops that are in a range of frames, moved relative to the start of this range
+    fn clip_loops(&self, start_frame: u64, end_frame: u64) -> Option<Vec<LoopInfo>> {
+        self.loop_info.as_ref().map(|loops| {
+            loops
+                .iter()
+                .filter(|l| l.loop_start < end_frame && l.loop_end >= start_frame)
+                .map(|l| LoopInfo {
+                    loop_start: l.loop_start.max(start_frame) - start_frame,
+                    loop_end: l.loop_end.min(end_frame - 1) - start_frame,
+                })
+                .collect()
+        })
+    }
+    /// Returns the broadcast metadata with its time reference moved to a frame
+    fn shifted_broadcast_info(&self, start_frame: u64) -> Option<BroadcastInfo> {
+        self.broadcast_info.as_ref().map(|b| BroadcastInfo {
+            time_reference: b.time_reference + start_frame,
+            ..b.clone()
+        })
+    }
+    /// Returns the cue points that are in a range of frames, moved relative to the start of this range
+    fn clip_markers(&self, start_frame: u64, end_frame: u64) -> Vec<CuePoint> {
+        self.markers
+            .iter()
+            .filter(|m| m.position >= start_frame && m.position < end_frame)
+            .map(|m| CuePoint {
+                position: m.position - start_frame,
+                ..m.clone()
+            })
+            .collect()
+    }
+}
+
+/// Returns true if a sample of a frame is at least level away from silence, in steps of its integer type
+fn is_loud_abs(frame: &Frame, level: i64) -> bool {
+    frame.samples.iter().any(|s| {
+        let distance = match s {
+            Sample::Unsigned8bits(v) => i64::from(*v) - 128,
+            Sample::Signed16bits(v) => i64::from(*v),
+            Sample::Signed24bits(v) => i64::from(v.value),
+            Sample::Signed32bits(v) => i64::from(*v),
+            _ => 0,
+        };
+        distance.abs() >= level
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {BTreeMap, CuePoint, Frame, LoopInfo, PCMParameters, Sample, PCM};
+    /// Mono PCM with the given values
+    fn mono(values: &[i16]) -> PCM {
+        PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: values
+                .iter()
+                .map(|v| Frame {
+                    samples: vec![Sample::Signed16bits(*v)],
+                })
+                .collect(),
+        }
+    }
+    #[test]
+    fn trim_silence() {
+        let mut pcm = mono(&[0, 10, 0, 20000, 0, 15000, 5, 0]);
+        assert_eq!(pcm.trim_silence(-40f64), (3, 2));
+        assert_eq!(pcm.frames.len(), 3);
+        match pcm.frames[1].samples[0] {
+            Sample::Signed16bits(v) => assert_eq!(v, 0),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn trim_silence_abs() {
+        let mut pcm = mono(&[0, 2, -3, 2, 0, 4, 1, -1]);
+        assert_eq!(pcm.trim_silence_abs(3).unwrap(), (2, 2));
+        assert_eq!(pcm.frames, mono(&[-3, 2, 0, 4]).frames);
+        let mut unsigned = mono(&[0, 0, 0]).convert_sample_type(Sample::Unsigned8bits(0));
+        unsigned.frames[1].samples[0] = Sample::Unsigned8bits(126);
+        assert_eq!(unsigned.trim_silence_abs(2).unwrap(), (1, 1));
+        let segments = mono(&[5, 0, 0, 0, 5, 1, 5])
+            .split_on_silence_abs(2, Duration::from_micros(375))
+            .unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].frames, mono(&[5, 1, 5]).frames);
+        let mut float = pcm.convert_sample_type(Sample::Float(0f32));
+        assert!(float.trim_silence_abs(1).is_err());
+    }
+    #[test]
+    fn pad() {
+        let mut pcm = mono(&[5, 6]);
+        pcm.pad_frames(2);
+        assert_eq!(pcm.frames.len(), 4);
+        assert_eq!(pcm.frames[3].samples, vec![Sample::Signed16bits(0)]);
+        pcm.pad_to_duration(Duration::from_millis(1));
+        assert_eq!(pcm.frames.len(), 8);
+        pcm.pad_to_duration(Duration::from_millis(0));
+        assert_eq!(pcm.frames.len(), 8);
+        let mut unsigned = pcm.convert_sample_type(Sample::Unsigned8bits(0));
+        unsigned.pad_frames(1);
+        assert_eq!(unsigned.frames[8].samples, vec![Sample::Unsigned8bits(128)]);
+    }
+    #[test]
+    fn truncate_to_duration() {
+        let mut pcm = mono(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        pcm.loop_info = Some(vec![
+            LoopInfo {
+                loop_start: 1,
+                loop_end: 6,
+            },
+            LoopInfo {
+                loop_start: 5,
+                loop_end: 6,
+            },
+        ]);
+        pcm.truncate_to_duration(Duration::from_secs(1));
+        assert_eq!(pcm.frames.len(), 8);
+        // 4.4 frames at 8000 Hz
+        pcm.truncate_to_duration(Duration::from_micros(550));
+        assert_eq!(pcm.frames, mono(&[0, 1, 2, 3]).frames);
+        let loops = pcm.loop_info.clone().unwrap();
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (1, 3));
+        pcm.truncate_to_duration(Duration::from_secs(0));
+        assert!(pcm.frames.is_empty());
+        assert!(pcm.loop_info.unwrap().is_empty());
+    }
+    #[test]
+    fn split_on_silence() {
+        let mut pcm = mono(&[0, 9000, 0, 9000, 0, 0, 0, 0, 5000, 0, 0, 0]);
+        pcm.loop_info = Some(vec![LoopInfo {
+            loop_start: 0,
+            loop_end: 9,
+        }]);
+        // 3 frames at 8000 Hz
+        let segments = pcm.split_on_silence(-20f64, Duration::from_micros(375));
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].frames, mono(&[0, 9000, 0, 9000]).frames);
+        assert_eq!(segments[1].frames, mono(&[5000]).frames);
+        assert!(segments.iter().all(|s| s.loop_info.is_none()));
+        assert!(mono(&[0, 0]).split_on_silence(-20f64, Duration::from_secs(0)).is_empty());
+    }
+    #[test]
+    fn slice() {
+        let mut pcm = mono(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        pcm.loop_info = Some(vec![
+            LoopInfo {
+                loop_start: 0,
+                loop_end: 1,
+            },
+            LoopInfo {
+                loop_start: 2,
+                loop_end: 8,
+            },
+        ]);
+        let slice = pcm.slice(3, 6).unwrap();
+        assert_eq!(slice.frames.len(), 3);
+        match slice.frames[0].samples[0] {
+            Sample::Signed16bits(v) => assert_eq!(v, 3),
+            _ => panic!(),
+        }
+        let loops = slice.loop_info.unwrap();
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (0, 2));
+        assert!(pcm.slice(6, 3).is_err());
+        assert!(pcm.slice(0, 11).is_err());
+    }
+    #[test]
+    fn append() {
+        let mut pcm = mono(&[0, 1, 2]);
+        let mut other = mono(&[3, 4]);
+        other.loop_info = Some(vec![LoopInfo {
+            loop_start: 0,
+            loop_end: 1,
+        }]);
+        pcm.append(&other).unwrap();
+        assert_eq!(pcm.frames.len(), 5);
+        let loops = pcm.loop_info.clone().unwrap();
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (3, 4));
+        other.parameters.sample_rate = 44100;
+        assert!(pcm.append(&other).is_err());
+        other.parameters.sample_rate = 8000;
+        let cue = |id| CuePoint {
+            id,
+            position: 1,
+            label: None,
+        };
+        pcm.markers = vec![cue(2), cue(u32::MAX)];
+        other.markers = vec![cue(u32::MAX), cue(7)];
+        pcm.append(&other).unwrap();
+        let ids: Vec<u32> = pcm.markers.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![2, u32::MAX, 0, 1]);
+        assert_eq!(pcm.markers[3].position, 6);
+    }
+    #[test]
+    fn reverse() {
+        let mut pcm = mono(&[0, 1, 2, 3, 4]);
+        pcm.loop_info = Some(vec![LoopInfo {
+            loop_start: 1,
+            loop_end: 2,
+        }]);
+        pcm.reverse();
+        match pcm.frames[0].samples[0] {
+            Sample::Signed16bits(v) => assert_eq!(v, 4),
+            _ => panic!(),
+        }
+        let loops = pcm.loop_info.clone().unwrap();
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (2, 3));
+        pcm.reverse();
+        let loops = pcm.loop_info.clone().unwrap();
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (1, 2));
+        let original = mono(&[0, 1, 2, 3, 4]);
+        assert_eq!(pcm.frames, original.frames);
+    }
+    #[test]
+    fn mix() {
+        let a = mono(&[1000, 20000, -30000]);
+        let b = mono(&[500, 20000, -10000]);
+        let clamped = PCM::mix(&[a.clone(), b.clone()], true).unwrap();
+        let values: Vec<Sample> = clamped.frames.iter().map(|f| f.samples[0].clone()).collect();
+        assert_eq!(
+            values,
+            vec![
+                Sample::Signed16bits(1500),
+                Sample::Signed16bits(32767),
+                Sample::Signed16bits(-32768),
+            ]
+        );
+        let scaled = PCM::mix(&[a.clone(), b], false).unwrap();
+        assert_eq!(scaled.frames[2].samples[0], Sample::Signed16bits(-32768));
+        assert_eq!(scaled.frames[1].samples[0], Sample::Signed16bits(32767));
+        assert_eq!(scaled.frames[0].samples[0], Sample::Signed16bits(1229));
+        assert!(PCM::mix(&[a, mono(&[0])], true).is_err());
+        assert!(PCM::mix(&[], true).is_err());
+    }
+    #[test]
+    fn crossfade() {
+        let a = mono(&[10000; 8]);
+        let b = mono(&[-10000; 8]);
+        // 4 frames at 8000 Hz
+        let joined = a.crossfade(&b, Duration::from_micros(500)).unwrap();
+        assert_eq!(joined.frames.len(), 12);
+        assert_eq!(joined.frames[3].samples[0], Sample::Signed16bits(10000));
+        assert_eq!(joined.frames[4].samples[0], Sample::Signed16bits(10000));
+        // Equal gains at the middle
+        assert_eq!(joined.frames[6].samples[0], Sample::Signed16bits(0));
+        assert_eq!(joined.frames[8].samples[0], Sample::Signed16bits(-10000));
+        let appended = a.crossfade(&b, Duration::from_secs(0)).unwrap();
+        assert_eq!(appended.frames.len(), 16);
+        let short = mono(&[0]);
+        assert!(a.crossfade(&short, Duration::from_micros(500)).is_err());
+        assert!(a.crossfade(&b, Duration::from_millis(2)).is_err());
+    }
+    #[test]
+    fn render_loops() {
+        let mut pcm = mono(&[0, 1, 2, 3, 4, 5]);
+        assert_eq!(pcm.render_loops(3).frames, pcm.frames);
+        pcm.loop_info = Some(vec![LoopInfo {
+            loop_start: 2,
+            loop_end: 3,
+        }]);
+        pcm.markers = vec![CuePoint {
+            id: 0,
+            position: 4,
+            label: None,
+        }];
+        let rendered = pcm.render_loops(2);
+        assert_eq!(
+            rendered.frames,
+            mono(&[0, 1, 2, 3, 2, 3, 2, 3, 4, 5]).frames
+        );
+        assert_eq!(rendered.markers[0].position, 8);
+        assert!(rendered.loop_info.is_none());
+        assert_eq!(pcm.render_loops(0).frames, pcm.frames);
+        // Nested loops
+        pcm.loop_info = Some(vec![
+            LoopInfo {
+                loop_start: 1,
+                loop_end: 4,
+            },
+            LoopInfo {
+                loop_start: 2,
+                loop_end: 2,
+            },
+        ]);
+        assert_eq!(
+            pcm.render_loops(1).frames,
+            mono(&[0, 1, 2, 2, 3, 4, 1, 2, 2, 3, 4, 5]).frames
+        );
+        // Overlapping loops
+        pcm.loop_info = Some(vec![
+            LoopInfo {
+                loop_start: 0,
+                loop_end: 2,
+            },
+            LoopInfo {
+                loop_start: 1,
+                loop_end: 3,
+            },
+        ]);
+        assert_eq!(
+            pcm.render_loops(1).frames,
+            mono(&[0, 1, 2, 0, 1, 2, 3, 1, 2, 3, 4, 5]).frames
+        );
+    }
+}
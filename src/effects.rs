@@ -0,0 +1,289 @@
+use std::time::Duration;
+use {Frame, PCMError, Result, Sample, PCM};
+
+impl PCM {
+    /// Changes the volume by a number of decibels, clamping integer samples instead of wrapping around
+    pub fn apply_gain(&mut self, db: f64) {
+        self.apply_gain_linear(10f64.powf(db / 20f64));
+    }
+    /// Multiplies every sample by a factor, clamping integer samples instead of wrapping around
+    pub fn apply_gain_linear(&mut self, factor: f64) {
+        for frame in &mut self.frames {
+            frame.apply_gain_linear(factor);
+        }
+    }
+    /// Changes the volume so that the loudest sample reaches a level in dBFS, does nothing on silence
+    pub fn normalize_peak(&mut self, target_db: f64) {
+        let peak = self.peak();
+        if peak > 0f64 {
+            self.apply_gain_linear(10f64.powf(target_db / 20f64) / peak);
+        }
+    }
+    /// Raises the volume linearly from silence over the start of the signal, the whole signal if it is shorter
+    pub fn fade_in(&mut self, duration: Duration) {
+        let nb_frames = self.fade_length(duration);
+        for (frame_id, frame) in self.frames.iter_mut().take(nb_frames).enumerate() {
+            frame.apply_gain_linear(frame_id as f64 / nb_frames as f64);
+        }
+    }
+    /// Lowers the volume linearly to silence over the end of the signal, the whole signal if it is shorter
+    pub fn fade_out(&mut self, duration: Duration) {
+        let nb_frames = self.fade_length(duration);
+        for (frame_id, frame) in self.frames.iter_mut().rev().take(nb_frames).enumerate() {
+            frame.apply_gain_linear(frame_id as f64 / nb_frames as f64);
+        }
+    }
+    /// Returns the number of frames a fade lasts for, clamped to the length of the signal
+    fn fade_length(&self, duration: Duration) -> usize {
+        ((duration.as_secs_f64() * f64::from(self.parameters.sample_rate)).round() as usize)
+            .min(self.frames.len())
+    }
+    /// Subtracts the mean value of each channel from its samples, clamping integer samples.
+    /// Returns the removed offset of each channel, in normalized values.
+    pub fn remove_dc_offset(&mut self) -> Vec<f64> {
+        let mut offsets = vec![0f64; self.parameters.nb_channels as usize];
+        if self.frames.is_empty() {
+            return offsets;
+        }
+        for frame in &self.frames {
+            for (offset, sample) in offsets.iter_mut().zip(frame.samples.iter()) {
+                *offset += sample.to_f64();
+            }
+        }
+        for offset in &mut offsets {
+            *offset /= self.frames.len() as f64;
+        }
+        for frame in &mut self.frames {
+            for (offset, sample) in offsets.iter().zip(frame.samples.iter_mut()) {
+                *sample = Sample::from_f64(sample.to_f64() - offset, sample);
+            }
+        }
+        offsets
+    }
+    /// Returns the difference between each sample and the previous one in the same channel, the first frame is kept as is.
+    /// This is a backward difference `x[n] - x[n-1]` keeping every frame so that integrate can undo it,
+    /// not the centered difference `x[n+1] - x[n-1]` over all frames but the first and last that the derive binary used to compute.
+    /// Integer samples are clamped if the difference goes out of range.
+    pub fn differentiate(&self) -> PCM {
+        let mut previous = vec![0f64; self.parameters.nb_channels as usize];
+        self.map_normalized(|channel, value| {
+            let difference = value - previous[channel];
+            previous[channel] = value;
+            difference
+        })
+    }
+    /// Returns the running sum of the samples in each channel, which undoes differentiate
+    pub fn integrate(&self) -> PCM {
+        let mut sum = vec![0f64; self.parameters.nb_channels as usize];
+        self.map_normalized(|channel, value| {
+            sum[channel] += value;
+            sum[channel]
+        })
+    }
+    /// Negates every sample, the minimum value of integer types saturating to the maximum
+    pub fn invert_polarity(&mut self) {
+        for frame in &mut self.frames {
+            for sample in &mut frame.samples {
+                *sample = sample.saturating_neg();
+            }
+        }
+    }
+    /// Applies a function to the normalized value of every sample, clamping integer samples
+    pub fn map_samples<F: Fn(f64) -> f64>(&mut self, function: F) {
+        for frame in &mut self.frames {
+            for sample in &mut frame.samples {
+                *sample = Sample::from_f64(function(sample.to_f64()), sample);
+            }
+        }
+    }
+    /// Applies a function to the normalized value of every sample of a single channel, clamping integer samples
+    pub fn map_samples_channel<F: Fn(f64) -> f64>(
+        &mut self,
+        channel: u16,
+        function: F,
+    ) -> Result<()> {
+        if channel >= self.parameters.nb_channels {
+            return Err(PCMError::ChannelOutOfRange(channel));
+        }
+        for frame in &mut self.frames {
+            let sample = &mut frame.samples[channel as usize];
+            *sample = Sample::from_f64(function(sample.to_f64()), sample);
+        }
+        Ok(())
+    }
+    /// Returns a copy where every sample went through a function taking its channel and its normalized value
+    fn map_normalized<F: FnMut(usize, f64) -> f64>(&self, mut function: F) -> PCM {
+        let mut pcm = self.clone();
+        for frame in &mut pcm.frames {
+            for (channel, sample) in frame.samples.iter_mut().enumerate() {
+                *sample = Sample::from_f64(function(channel, sample.to_f64()), sample);
+            }
+        }
+        pcm
+    }
+}
+
+impl Frame {
+    /// Multiplies every sample of this frame by a factor, clamping integer samples
+    fn apply_gain_linear(&mut self, factor: f64) {
+        for sample in &mut self.samples {
+            *sample = Sample::from_f64(sample.to_f64() * factor, sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {BTreeMap, Frame, PCMParameters, Sample, PCM};
+    /// Stereo PCM with a single frame
+    fn single_frame(left: i16, right: i16) -> PCM {
+        PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![Frame {
+                samples: vec![Sample::Signed16bits(left), Sample::Signed16bits(right)],
+            }],
+        }
+    }
+    #[test]
+    fn apply_gain() {
+        let mut pcm = single_frame(10000, -30000);
+        pcm.apply_gain(-6.0206);
+        match (&pcm.frames[0].samples[0], &pcm.frames[0].samples[1]) {
+            (Sample::Signed16bits(l), Sample::Signed16bits(r)) => assert_eq!((*l, *r), (5000, -15000)),
+            _ => panic!(),
+        }
+        pcm.apply_gain_linear(4f64);
+        match (&pcm.frames[0].samples[0], &pcm.frames[0].samples[1]) {
+            (Sample::Signed16bits(l), Sample::Signed16bits(r)) => {
+                assert_eq!((*l, *r), (20000, <i16>::min_value()))
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn normalize_peak() {
+        let mut pcm = single_frame(8000, -16000);
+        pcm.normalize_peak(0f64);
+        match (&pcm.frames[0].samples[0], &pcm.frames[0].samples[1]) {
+            (Sample::Signed16bits(l), Sample::Signed16bits(r)) => {
+                assert_eq!((*l, *r), (16384, <i16>::min_value()))
+            }
+            _ => panic!(),
+        }
+        let mut silence = single_frame(0, 0);
+        silence.normalize_peak(-1f64);
+        match silence.frames[0].samples[0] {
+            Sample::Signed16bits(v) => assert_eq!(v, 0),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn differentiate_then_integrate() {
+        let mut pcm = single_frame(1000, -1000);
+        for &(left, right) in &[(3000, -500), (-2000, 0)] {
+            pcm.frames.push(Frame {
+                samples: vec![Sample::Signed16bits(left), Sample::Signed16bits(right)],
+            });
+        }
+        let derived = pcm.differentiate();
+        let values: Vec<(i16, i16)> = derived
+            .frames
+            .iter()
+            .map(|f| match (&f.samples[0], &f.samples[1]) {
+                (Sample::Signed16bits(l), Sample::Signed16bits(r)) => (*l, *r),
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(values, vec![(1000, -1000), (2000, 500), (-5000, 500)]);
+        let integrated = derived.integrate();
+        assert_eq!(integrated.frames, pcm.frames);
+    }
+    #[test]
+    fn fades() {
+        let mut pcm = single_frame(10000, -10000);
+        pcm.frames = vec![pcm.frames[0].clone(); 8];
+        pcm.parameters.sample_rate = 1000;
+        pcm.fade_in(Duration::from_millis(4));
+        pcm.fade_out(Duration::from_millis(2));
+        let left: Vec<i16> = pcm.frames
+            .iter()
+            .map(|f| match f.samples[0] {
+                Sample::Signed16bits(v) => v,
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(left, vec![0, 2500, 5000, 7500, 10000, 10000, 5000, 0]);
+        let mut short = single_frame(10000, -10000);
+        short.fade_in(Duration::from_secs(1));
+        match short.frames[0].samples[1] {
+            Sample::Signed16bits(v) => assert_eq!(v, 0),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn map_samples() {
+        let mut pcm = single_frame(10000, -10000);
+        pcm.map_samples(|v| v * 4f64);
+        assert_eq!(
+            pcm.frames[0].samples,
+            vec![
+                Sample::Signed16bits(<i16>::max_value()),
+                Sample::Signed16bits(<i16>::min_value())
+            ]
+        );
+        let mut pcm = single_frame(10000, -10000);
+        pcm.map_samples_channel(1, |v| -v).unwrap();
+        assert_eq!(
+            pcm.frames[0].samples,
+            vec![Sample::Signed16bits(10000), Sample::Signed16bits(10000)]
+        );
+        assert!(pcm.map_samples_channel(2, |v| v).is_err());
+    }
+    #[test]
+    fn invert_polarity() {
+        let mut pcm = single_frame(1234, -32767);
+        let original = pcm.clone();
+        pcm.invert_polarity();
+        assert_eq!(
+            pcm.frames[0].samples,
+            vec![Sample::Signed16bits(-1234), Sample::Signed16bits(32767)]
+        );
+        pcm.invert_polarity();
+        assert_eq!(pcm, original);
+        let mut edges = single_frame(<i16>::min_value(), 0);
+        edges.invert_polarity();
+        assert_eq!(edges.frames[0].samples[0], Sample::Signed16bits(<i16>::max_value()));
+        for value in 1..=255u8 {
+            let inverted = Sample::Unsigned8bits(value).saturating_neg();
+            assert_eq!(inverted.saturating_neg(), Sample::Unsigned8bits(value));
+        }
+        assert_eq!(Sample::Unsigned8bits(128).saturating_neg(), Sample::Unsigned8bits(128));
+        assert_eq!(Sample::Unsigned8bits(0).saturating_neg(), Sample::Unsigned8bits(255));
+    }
+    #[test]
+    fn remove_dc_offset() {
+        let mut pcm = single_frame(1000, -2000);
+        pcm.frames.push(Frame {
+            samples: vec![Sample::Signed16bits(3000), Sample::Signed16bits(0)],
+        });
+        let offsets = pcm.remove_dc_offset();
+        assert!((offsets[0] - 2000f64 / 32768f64).abs() < 1e-9);
+        assert!((offsets[1] + 1000f64 / 32768f64).abs() < 1e-9);
+        match (&pcm.frames[0].samples[0], &pcm.frames[1].samples[1]) {
+            (Sample::Signed16bits(l), Sample::Signed16bits(r)) => {
+                assert_eq!((*l, *r), (-1000, 1000))
+            }
+            _ => panic!(),
+        }
+    }
+}
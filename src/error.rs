@@ -1,58 +1,220 @@
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::String};
+use core::fmt;
+#[cfg(feature = "flac")]
+use claxon::Error as FlacError;
+#[cfg(feature = "std")]
 use magic_number::MagicNumberCheckError;
+#[cfg(feature = "std")]
 use std::error::Error;
-use std::fmt;
-use std::io::Error as IoError;
+#[cfg(feature = "std")]
+use std::io::{Error as IoError, ErrorKind};
+use Sample;
 
 #[derive(Debug)]
 pub enum PCMError {
+    #[cfg(feature = "std")]
     IoError(IoError),
+    #[cfg(feature = "std")]
     WrongMagicNumber(MagicNumberCheckError),
     UnknownFormat(u16),
     UnknownBitsPerSample(u16),
     TooMuchData(usize),
     TooManyFrames(usize),
+    UnknownADPCMPredictor(u8),
+    MissingChunk([u8; 4]),
+    ChannelOutOfRange(u16),
+    IncompatibleParameters(&'static str),
+    InvalidParameters(&'static str),
+    InvalidFrameRange(u64, u64),
+    PartialFrame(usize),
+    UnsupportedSampleType(Sample),
+    ParseError {
+        offset: u64,
+        chunk: [u8; 4],
+        kind: Box<PCMError>,
+    },
+    WrongNumberOfSamples(usize, usize),
+    WrongSampleType(usize, Sample),
+    InvalidBlockAlign(u16),
+    InvalidHeader(&'static str),
+    TruncatedData { expected: u64, actual: u64 },
+    SampleOutOfRange(i32, Sample),
+    #[cfg(feature = "flac")]
+    FlacError(FlacError),
 }
 
+#[cfg(feature = "std")]
 impl Error for PCMError {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            PCMError::IoError(e) => e.description(),
-            PCMError::WrongMagicNumber(e) => e.description(),
-            PCMError::UnknownFormat(_) => "Unknown format field value in Wave Header",
-            PCMError::UnknownBitsPerSample(_) => {
-                "Cannot infer information about a Bits per Sample in Wave header"
+            PCMError::IoError(e) => Some(e),
+            PCMError::WrongMagicNumber(e) => Some(e),
+            PCMError::ParseError { kind, .. } => Some(kind.as_ref()),
+            #[cfg(feature = "flac")]
+            PCMError::FlacError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PCMError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            PCMError::IoError(_) => write!(f, "I/O error while reading PCM data"),
+            #[cfg(feature = "std")]
+            PCMError::WrongMagicNumber(_) => {
+                write!(f, "File does not start with the expected magic number")
+            }
+            PCMError::UnknownFormat(v) => write!(f, "Unknown format field value in header: {}", v),
+            PCMError::UnknownBitsPerSample(b) => {
+                write!(f, "Cannot infer sample type from {} bits per sample", b)
             }
-            PCMError::TooMuchData(_) => {
-                "Number of bytes composing audio is too ig to fit in a u32 number"
+            PCMError::TooMuchData(s) => {
+                write!(f, "{} bytes of audio data are too many to fit in a u32", s)
             }
-            PCMError::TooManyFrames(_) => {
-                "Number of frames is too big to fit in a u32 to write Fact chunk"
+            PCMError::TooManyFrames(s) => write!(f, "{} frames are too many to fit in a u32", s),
+            PCMError::UnknownADPCMPredictor(p) => {
+                write!(f, "ADPCM block uses predictor {} that is not in the format chunk", p)
             }
+            PCMError::MissingChunk(c) => write!(f, "Missing chunk: {}", String::from_utf8_lossy(c)),
+            PCMError::ChannelOutOfRange(c) => write!(f, "Channel {} does not exist", c),
+            PCMError::IncompatibleParameters(p) => write!(f, "PCMs have mismatched {}", p),
+            PCMError::InvalidParameters(p) => write!(f, "PCM parameters cannot have {}", p),
+            PCMError::InvalidFrameRange(s, e) => {
+                write!(f, "Frames {} to {} are reversed or go past the last frame", s, e)
+            }
+            PCMError::PartialFrame(s) => write!(f, "Data ends with a partial frame of {} bytes", s),
+            PCMError::UnsupportedSampleType(s) => write!(f, "{} samples are not supported here", s),
+            PCMError::ParseError { offset, chunk, .. } => write!(
+                f,
+                "In chunk {} at byte {}",
+                String::from_utf8_lossy(chunk),
+                offset
+            ),
+            PCMError::WrongNumberOfSamples(frame, n) => {
+                write!(f, "Frame {} has {} samples instead of one per channel", frame, n)
+            }
+            PCMError::WrongSampleType(frame, s) => {
+                write!(f, "Frame {} holds {} samples instead of the declared type", frame, s)
+            }
+            PCMError::InvalidBlockAlign(b) => {
+                write!(f, "Block align of {} bytes does not fit whole ADPCM blocks", b)
+            }
+            PCMError::InvalidHeader(h) => write!(f, "Header declares {}", h),
+            PCMError::TruncatedData { expected, actual } => write!(
+                f,
+                "Expected {} bytes of data but the stream ended after {}",
+                expected, actual
+            ),
+            PCMError::SampleOutOfRange(v, s) => {
+                write!(f, "Value {} does not fit in {} samples", v, s)
+            }
+            #[cfg(feature = "flac")]
+            PCMError::FlacError(_) => write!(f, "FLAC stream could not be decoded"),
         }
     }
 }
 
-impl fmt::Display for PCMError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl PCMError {
+    #[cfg(feature = "std")]
+    /// Returns the kind of I/O error this error is closest to
+    fn io_error_kind(&self) -> ErrorKind {
         match self {
-            PCMError::IoError(e) => e.fmt(f),
-            PCMError::WrongMagicNumber(e) => e.fmt(f),
-            PCMError::UnknownFormat(v) => write!(f, "Unrecognized {}", v),
-            PCMError::UnknownBitsPerSample(b) => write!(f, "Bits per Sample: {}", b),
-            PCMError::TooMuchData(s) => write!(f, "Tried to write {} bytes of data", s),
-            PCMError::TooManyFrames(s) => write!(f, "Tried to write {} frames", s),
+            PCMError::IoError(e) => e.kind(),
+            PCMError::ParseError { kind, .. } => kind.io_error_kind(),
+            PCMError::TruncatedData { .. } => ErrorKind::UnexpectedEof,
+            PCMError::WrongMagicNumber(_)
+            | PCMError::UnknownFormat(_)
+            | PCMError::UnknownBitsPerSample(_)
+            | PCMError::UnknownADPCMPredictor(_)
+            | PCMError::MissingChunk(_)
+            | PCMError::PartialFrame(_)
+            | PCMError::InvalidBlockAlign(_)
+            | PCMError::InvalidHeader(_)
+            | PCMError::SampleOutOfRange(..) => ErrorKind::InvalidData,
+            #[cfg(feature = "flac")]
+            PCMError::FlacError(_) => ErrorKind::InvalidData,
+            _ => ErrorKind::InvalidInput,
+        }
+    }
+    #[cfg(feature = "std")]
+    /// Wraps this error with the ID and the offset of the chunk it happened in, offset being where the chunk ID is
+    pub(crate) fn in_chunk(self, chunk: [u8; 4], offset: u64) -> PCMError {
+        match self {
+            PCMError::ParseError { .. } => self,
+            kind => PCMError::ParseError {
+                offset,
+                chunk,
+                kind: Box::new(kind),
+            },
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<IoError> for PCMError {
     fn from(e: IoError) -> PCMError {
         PCMError::IoError(e)
     }
 }
 
+#[cfg(feature = "std")]
+impl From<PCMError> for IoError {
+    /// Errors in the data read become InvalidData, errors in the arguments become InvalidInput
+    fn from(e: PCMError) -> IoError {
+        match e {
+            PCMError::IoError(e) => e,
+            e => IoError::new(e.io_error_kind(), e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<MagicNumberCheckError> for PCMError {
     fn from(e: MagicNumberCheckError) -> PCMError {
         PCMError::WrongMagicNumber(e)
     }
 }
+
+#[cfg(feature = "flac")]
+impl From<FlacError> for PCMError {
+    fn from(e: FlacError) -> PCMError {
+        PCMError::FlacError(e)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::PCMError;
+    use std::error::Error;
+    use std::io::{Error as IoError, ErrorKind};
+    #[test]
+    fn error_chain() {
+        let error = PCMError::from(IoError::new(ErrorKind::UnexpectedEof, "end of file"))
+            .in_chunk(*b"data", 36);
+        assert_eq!(error.to_string(), "In chunk data at byte 36");
+        let kind = error.source().unwrap();
+        assert_eq!(kind.to_string(), "I/O error while reading PCM data");
+        let io_error = kind.source().unwrap();
+        assert_eq!(io_error.to_string(), "end of file");
+        assert!(PCMError::MissingChunk(*b"fmt ").source().is_none());
+    }
+    #[test]
+    fn into_io_error() {
+        let truncated = PCMError::TruncatedData {
+            expected: 8,
+            actual: 5,
+        }
+        .in_chunk(*b"data", 36);
+        assert_eq!(IoError::from(truncated).kind(), ErrorKind::UnexpectedEof);
+        let missing = IoError::from(PCMError::MissingChunk(*b"data"));
+        assert_eq!(missing.kind(), ErrorKind::InvalidData);
+        assert_eq!(missing.to_string(), "Missing chunk: data");
+        let channel = IoError::from(PCMError::ChannelOutOfRange(2));
+        assert_eq!(channel.kind(), ErrorKind::InvalidInput);
+        let io = IoError::from(PCMError::from(IoError::new(ErrorKind::NotFound, "no file")));
+        assert_eq!(io.kind(), ErrorKind::NotFound);
+    }
+}
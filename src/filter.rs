@@ -0,0 +1,134 @@
+use std::f64::consts::PI;
+use {Sample, PCM};
+
+/// Normalized coefficients of a biquad filter, a0 being 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BiquadCoeffs {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl BiquadCoeffs {
+    /// Low-pass filter removing frequencies above cutoff, in Hz
+    pub fn low_pass(sample_rate: u32, cutoff: f64, q: f64) -> BiquadCoeffs {
+        let (alpha, cos) = BiquadCoeffs::intermediates(sample_rate, cutoff, q);
+        BiquadCoeffs::normalize(
+            (1f64 - cos) / 2f64,
+            1f64 - cos,
+            (1f64 - cos) / 2f64,
+            1f64 + alpha,
+            -2f64 * cos,
+            1f64 - alpha,
+        )
+    }
+    /// High-pass filter removing frequencies below cutoff, in Hz
+    pub fn high_pass(sample_rate: u32, cutoff: f64, q: f64) -> BiquadCoeffs {
+        let (alpha, cos) = BiquadCoeffs::intermediates(sample_rate, cutoff, q);
+        BiquadCoeffs::normalize(
+            (1f64 + cos) / 2f64,
+            -(1f64 + cos),
+            (1f64 + cos) / 2f64,
+            1f64 + alpha,
+            -2f64 * cos,
+            1f64 - alpha,
+        )
+    }
+    /// Band-pass filter keeping frequencies around center, in Hz, with a gain of 0 dB at center
+    pub fn band_pass(sample_rate: u32, center: f64, q: f64) -> BiquadCoeffs {
+        let (alpha, cos) = BiquadCoeffs::intermediates(sample_rate, center, q);
+        BiquadCoeffs::normalize(
+            alpha,
+            0f64,
+            -alpha,
+            1f64 + alpha,
+            -2f64 * cos,
+            1f64 - alpha,
+        )
+    }
+    /// Returns alpha and the cosine of the angular frequency, as defined in the Audio EQ Cookbook
+    fn intermediates(sample_rate: u32, frequency: f64, q: f64) -> (f64, f64) {
+        let omega = 2f64 * PI * frequency / f64::from(sample_rate);
+        (omega.sin() / (2f64 * q), omega.cos())
+    }
+    /// Divides all coefficients by a0
+    fn normalize(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> BiquadCoeffs {
+        BiquadCoeffs {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// Last two inputs and outputs of a channel
+#[derive(Clone, Copy, Default)]
+struct BiquadState {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl PCM {
+    /// Filters every channel with a Direct Form I biquad, clamping integer samples
+    pub fn apply_biquad(&mut self, coeffs: BiquadCoeffs) {
+        let mut states = vec![BiquadState::default(); self.parameters.nb_channels as usize];
+        for frame in &mut self.frames {
+            for (state, sample) in states.iter_mut().zip(frame.samples.iter_mut()) {
+                let x = sample.to_f64();
+                let y = coeffs.b0 * x + coeffs.b1 * state.x1 + coeffs.b2 * state.x2
+                    - coeffs.a1 * state.y1
+                    - coeffs.a2 * state.y2;
+                *state = BiquadState {
+                    x1: x,
+                    x2: state.x1,
+                    y1: y,
+                    y2: state.y1,
+                };
+                *sample = Sample::from_f64(y, sample);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BiquadCoeffs;
+    use std::time::Duration;
+    use {Sample, PCM};
+    /// Returns the peak of a filtered sine, ignoring the start while the filter settles
+    fn filtered_peak(freq: f64, coeffs: BiquadCoeffs) -> f64 {
+        let mut pcm = PCM::generate_sine(
+            48000,
+            freq,
+            Duration::from_millis(100),
+            0.5,
+            Sample::Float(0f32),
+        );
+        pcm.apply_biquad(coeffs);
+        pcm.frames[2400..]
+            .iter()
+            .map(|f| f.samples[0].to_f64().abs())
+            .fold(0f64, f64::max)
+    }
+    #[test]
+    fn low_pass() {
+        let coeffs = BiquadCoeffs::low_pass(48000, 1000f64, 0.7071);
+        assert!((filtered_peak(100f64, coeffs) - 0.5).abs() < 0.01);
+        assert!(filtered_peak(10000f64, coeffs) < 0.01);
+    }
+    #[test]
+    fn high_and_band_pass() {
+        let high = BiquadCoeffs::high_pass(48000, 1000f64, 0.7071);
+        assert!(filtered_peak(100f64, high) < 0.01);
+        assert!((filtered_peak(10000f64, high) - 0.5).abs() < 0.01);
+        let band = BiquadCoeffs::band_pass(48000, 1000f64, 2f64);
+        assert!((filtered_peak(1000f64, band) - 0.5).abs() < 0.01);
+        assert!(filtered_peak(10000f64, band) < 0.05);
+    }
+}
@@ -0,0 +1,144 @@
+use claxon::FlacReader;
+use sample_types::I24;
+use std::io::{Read, Seek};
+use std::mem;
+use {BTreeMap, Frame, PCMError, PCMParameters, Result, Sample, PCM};
+
+impl PCM {
+    /// Imports a FLAC file, samples being Signed 16 bits up to 16 bits per sample, Signed 24 bits up to 24 and Signed 32 bits above.
+    /// Samples with fewer bits than their type are shifted left to keep the same level.
+    pub fn import_flac_file<R: Read + Seek>(reader: &mut R) -> Result<PCM> {
+        let mut flac_reader = FlacReader::new(reader)?;
+        let info = flac_reader.streaminfo();
+        let (sample_type, shift) = match info.bits_per_sample {
+            b if b <= 16 => (Sample::Signed16bits(0), 16 - b),
+            b if b <= 24 => (Sample::Signed24bits(I24 { value: 0 }), 24 - b),
+            b => (Sample::Signed32bits(0), 32 - b.min(32)),
+        };
+        let nb_channels = info.channels as usize;
+        // The number of frames in the stream info cannot be trusted to allocate memory
+        let mut frames = Vec::new();
+        let mut samples = Vec::with_capacity(nb_channels);
+        for value in flac_reader.samples() {
+            let value = value? << shift;
+            samples.push(match sample_type {
+                Sample::Signed16bits(_) => Sample::Signed16bits(value as i16),
+                Sample::Signed24bits(_) => Sample::Signed24bits(I24 { value }),
+                _ => Sample::Signed32bits(value),
+            });
+            if samples.len() == nb_channels {
+                frames.push(Frame {
+                    samples: mem::replace(&mut samples, Vec::with_capacity(nb_channels)),
+                });
+            }
+        }
+        if !samples.is_empty() {
+            return Err(PCMError::PartialFrame(
+                samples.len() * (sample_type.get_binary_size() / 8) as usize,
+            ));
+        }
+        Ok(PCM {
+            parameters: PCMParameters {
+                sample_rate: info.sample_rate,
+                nb_channels: info.channels as u16,
+                sample_type,
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sample_types::I24;
+    use std::io::Cursor;
+    use {Frame, Sample, PCM};
+    /// Wraps each list of samples into a frame
+    fn frames(samples: Vec<Vec<Sample>>) -> Vec<Frame> {
+        samples
+            .into_iter()
+            .map(|samples| Frame { samples })
+            .collect()
+    }
+    #[test]
+    fn import_16_bits_stereo() {
+        // 44100 Hz 16 bits stereo, one block of 4 frames stored in verbatim subframes
+        let flac = [
+            0x66, 0x4C, 0x61, 0x43, 0x80, 0x00, 0x00, 0x22, 0x00, 0x10, 0x00, 0x10, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x0A, 0xC4, 0x42, 0xF0, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xFF, 0xF8, 0x60, 0x18, 0x00, 0x03, 0x10, 0x02, 0x03, 0xE8, 0xFF, 0xFF, 0x7F, 0xFF,
+            0x80, 0x00, 0x02, 0xFC, 0x18, 0x01, 0x00, 0x00, 0x00, 0x30, 0x39, 0xA3, 0x07,
+        ];
+        let pcm = PCM::import_flac_file(&mut Cursor::new(&flac[..])).unwrap();
+        assert_eq!(pcm.parameters.sample_rate, 44100);
+        assert_eq!(pcm.parameters.nb_channels, 2);
+        assert_eq!(pcm.parameters.sample_type, Sample::Signed16bits(0));
+        let expected = [(1000, -1000), (-1, 256), (32767, 0), (-32768, 12345)];
+        assert_eq!(
+            pcm.frames,
+            frames(
+                expected
+                    .iter()
+                    .map(|&(l, r)| vec![Sample::Signed16bits(l), Sample::Signed16bits(r)])
+                    .collect()
+            )
+        );
+    }
+    #[test]
+    fn import_odd_bit_depths() {
+        // Mono 12 bits samples 2047, -2048, 1, -1
+        let flac_12 = [
+            0x66, 0x4C, 0x61, 0x43, 0x80, 0x00, 0x00, 0x22, 0x00, 0x10, 0x00, 0x10, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x01, 0xF4, 0x00, 0xB0, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xFF, 0xF8, 0x60, 0x04, 0x00, 0x03, 0x48, 0x02, 0x7F, 0xF8, 0x00, 0x00, 0x1F, 0xFF,
+            0x26, 0x04,
+        ];
+        let pcm = PCM::import_flac_file(&mut Cursor::new(&flac_12[..])).unwrap();
+        assert_eq!(pcm.parameters.sample_rate, 8000);
+        assert_eq!(pcm.parameters.nb_channels, 1);
+        assert_eq!(pcm.parameters.sample_type, Sample::Signed16bits(0));
+        assert_eq!(
+            pcm.frames,
+            frames(
+                [32752, -32768, 16, -16]
+                    .iter()
+                    .map(|&v| vec![Sample::Signed16bits(v)])
+                    .collect()
+            )
+        );
+        // Mono 20 bits samples 0x7FFFF, -0x80000, 1, -2
+        let flac_20 = [
+            0x66, 0x4C, 0x61, 0x43, 0x80, 0x00, 0x00, 0x22, 0x00, 0x10, 0x00, 0x10, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x01, 0xF4, 0x01, 0x30, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xFF, 0xF8, 0x60, 0x0A, 0x00, 0x03, 0x64, 0x02, 0x7F, 0xFF, 0xF8, 0x00, 0x00, 0x00,
+            0x00, 0x1F, 0xFF, 0xFE, 0xEB, 0x29,
+        ];
+        let pcm = PCM::import_flac_file(&mut Cursor::new(&flac_20[..])).unwrap();
+        assert_eq!(
+            pcm.parameters.sample_type,
+            Sample::Signed24bits(I24 { value: 0 })
+        );
+        assert_eq!(
+            pcm.frames,
+            frames(
+                [0x7F_FFF0, -0x80_0000, 16, -32]
+                    .iter()
+                    .map(|&value| vec![Sample::Signed24bits(I24 { value })])
+                    .collect()
+            )
+        );
+    }
+    #[test]
+    fn reject_other_formats() {
+        let mut wave = Cursor::new(b"RIFF\x04\x00\x00\x00WAVE".to_vec());
+        assert!(PCM::import_flac_file(&mut wave).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use {Frame, PCMError, Result, Sample};
+
+/// Biggest magnitude a µ-law byte can hold, before adding the bias
+const MU_LAW_CLIP: i32 = 32_635;
+
+/// Added to µ-law magnitudes so that every segment starts on a power of two
+const MU_LAW_BIAS: i32 = 0x84;
+
+/// Expands an A-law byte into a 16 bits sample
+pub fn decode_a_law(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let mantissa = i32::from(byte & 0x0F) << 4;
+    let exponent = (byte >> 4) & 0x07;
+    let magnitude = match exponent {
+        0 => mantissa + 8,
+        e => (mantissa + 0x108) << (e - 1),
+    };
+    // The sign bit is set for positive values
+    if byte & 0x80 != 0 {
+        magnitude as i16
+    } else {
+        -magnitude as i16
+    }
+}
+
+/// Compresses a 16 bits sample into an A-law byte
+pub fn encode_a_law(sample: i16) -> u8 {
+    let (mask, magnitude) = if sample >= 0 {
+        (0xD5, i32::from(sample) >> 3)
+    } else {
+        (0x55, -(i32::from(sample) >> 3) - 1)
+    };
+    let exponent = (0..8).find(|e| magnitude < (0x20 << e)).unwrap_or(8);
+    let byte = match exponent {
+        8 => 0x7F,
+        0 | 1 => (exponent << 4) as u8 | ((magnitude >> 1) & 0x0F) as u8,
+        e => (e << 4) as u8 | ((magnitude >> e) & 0x0F) as u8,
+    };
+    byte ^ mask
+}
+
+/// Expands a µ-law byte into a 16 bits sample
+pub fn decode_mu_law(byte: u8) -> i16 {
+    let byte = !byte;
+    let exponent = (byte >> 4) & 0x07;
+    let magnitude = ((i32::from(byte & 0x0F) << 3) + MU_LAW_BIAS) << exponent;
+    if byte & 0x80 != 0 {
+        (MU_LAW_BIAS - magnitude) as i16
+    } else {
+        (magnitude - MU_LAW_BIAS) as i16
+    }
+}
+
+/// Compresses a 16 bits sample into a µ-law byte
+pub fn encode_mu_law(sample: i16) -> u8 {
+    let sign = if sample < 0 { 0x80 } else { 0 };
+    let magnitude = i32::from(sample).abs().min(MU_LAW_CLIP) + MU_LAW_BIAS;
+    let exponent = (0..8).rev().find(|e| magnitude & (0x80 << e) != 0).unwrap_or(0);
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+    !(sign | (exponent << 4) as u8 | mantissa as u8)
+}
+
+#[cfg(feature = "std")]
+/// Compresses frames of Signed 16 bits samples into A-law or µ-law bytes, depending on the type of law
+pub fn encode<W: Write>(writer: &mut W, frames: &[Frame], law: &Sample) -> Result<()> {
+    let encode_sample: fn(i16) -> u8 = match law {
+        Sample::ALaw(_) => encode_a_law,
+        Sample::MuLaw(_) => encode_mu_law,
+        s => return Err(PCMError::UnsupportedSampleType(s.clone())),
+    };
+    for frame in frames {
+        let bytes = frame
+            .samples
+            .iter()
+            .map(|s| match s {
+                Sample::Signed16bits(v) => Ok(encode_sample(*v)),
+                s => Err(PCMError::UnsupportedSampleType(s.clone())),
+            })
+            .collect::<Result<Vec<u8>>>()?;
+        writer.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn known_values() {
+        assert_eq!(decode_a_law(0xD5), 8);
+        assert_eq!(decode_a_law(0x55), -8);
+        assert_eq!(decode_a_law(0xAA), 32256);
+        assert_eq!(decode_mu_law(0xFF), 0);
+        assert_eq!(decode_mu_law(0x80), 32124);
+        assert_eq!(decode_mu_law(0x00), -32124);
+        assert_eq!(encode_a_law(<i16>::max_value()), 0xAA);
+        assert_eq!(encode_mu_law(<i16>::min_value()), 0x00);
+    }
+    #[test]
+    fn encode_then_decode() {
+        for byte in 0..=255u8 {
+            let a_law = decode_a_law(byte);
+            assert_eq!(encode_a_law(a_law), byte);
+            let mu_law = decode_mu_law(byte);
+            assert_eq!(decode_mu_law(encode_mu_law(mu_law)), mu_law);
+        }
+        for sample in (<i16>::min_value()..=<i16>::max_value()).step_by(7) {
+            let error = (i32::from(decode_a_law(encode_a_law(sample))) - i32::from(sample)).abs();
+            assert!(error <= 1024);
+            let error = (i32::from(decode_mu_law(encode_mu_law(sample))) - i32::from(sample)).abs();
+            assert!(error <= 1024);
+        }
+    }
+}
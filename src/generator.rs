@@ -0,0 +1,65 @@
+use std::f64::consts::PI;
+use std::time::Duration;
+use {BTreeMap, Frame, PCMParameters, Sample, PCM};
+
+impl PCM {
+    /// Generates a mono sine tone, amplitude being between 0 and 1
+    pub fn generate_sine(
+        sample_rate: u32,
+        freq: f64,
+        duration: Duration,
+        amplitude: f64,
+        sample_type: Sample,
+    ) -> PCM {
+        let nb_frames = (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize;
+        let frames = (0..nb_frames)
+            .map(|i| {
+                let t = i as f64 / f64::from(sample_rate);
+                Frame {
+                    samples: vec![Sample::from_f64(
+                        (2f64 * PI * freq * t).sin() * amplitude,
+                        &sample_type,
+                    )],
+                }
+            })
+            .collect();
+        PCM {
+            parameters: PCMParameters {
+                sample_rate,
+                nb_channels: 1,
+                sample_type,
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use {Sample, PCM};
+    #[test]
+    fn sine() {
+        let pcm = PCM::generate_sine(
+            8000,
+            2000f64,
+            Duration::from_millis(500),
+            0.5,
+            Sample::Signed16bits(0),
+        );
+        assert_eq!(pcm.frames.len(), 4000);
+        // A quarter of the period is exactly one frame
+        let expected = [0i16, 16384, 0, -16384];
+        for (frame, expected) in pcm.frames.iter().zip(expected.iter()) {
+            match frame.samples[0] {
+                Sample::Signed16bits(v) => assert!((v - expected).abs() <= 1),
+                _ => panic!(),
+            }
+        }
+    }
+}
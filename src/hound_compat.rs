@@ -0,0 +1,171 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::mem;
+use hound::{SampleFormat, WavSpec};
+use sample_types::I24;
+use {BTreeMap, Frame, PCMError, PCMParameters, Result, Sample, PCM};
+
+/// A sample as given by hound, 8 bits samples being centered on 0
+pub trait HoundSample: Copy {
+    /// Converts this sample to the given type, which must be the one matching the hound spec
+    fn to_sample(self, sample_type: &Sample) -> Result<Sample>;
+}
+
+impl HoundSample for i8 {
+    fn to_sample(self, sample_type: &Sample) -> Result<Sample> {
+        i32::from(self).to_sample(sample_type)
+    }
+}
+
+impl HoundSample for i16 {
+    fn to_sample(self, sample_type: &Sample) -> Result<Sample> {
+        i32::from(self).to_sample(sample_type)
+    }
+}
+
+impl HoundSample for i32 {
+    fn to_sample(self, sample_type: &Sample) -> Result<Sample> {
+        let (min, max) = match sample_type {
+            Sample::Unsigned8bits(_) => (-128, 127),
+            Sample::Signed16bits(_) => {
+                (i32::from(<i16>::min_value()), i32::from(<i16>::max_value()))
+            }
+            Sample::Signed24bits(_) => (-0x80_0000, 0x7F_FFFF),
+            _ => (<i32>::min_value(), <i32>::max_value()),
+        };
+        if self < min || self > max {
+            return Err(PCMError::SampleOutOfRange(self, sample_type.clone()));
+        }
+        Ok(match sample_type {
+            Sample::Unsigned8bits(_) => Sample::Unsigned8bits((self + 128) as u8),
+            Sample::Signed16bits(_) => Sample::Signed16bits(self as i16),
+            Sample::Signed24bits(_) => Sample::Signed24bits(I24 { value: self }),
+            Sample::Signed32bits(_) => Sample::Signed32bits(self),
+            s => return Err(PCMError::UnsupportedSampleType(s.clone())),
+        })
+    }
+}
+
+impl HoundSample for f32 {
+    fn to_sample(self, sample_type: &Sample) -> Result<Sample> {
+        match sample_type {
+            Sample::Float(_) => Ok(Sample::Float(self)),
+            s => Err(PCMError::UnsupportedSampleType(s.clone())),
+        }
+    }
+}
+
+impl PCMParameters {
+    /// Returns the hound spec describing the same samples, only integer types and 32 bits floats are supported
+    pub fn to_hound_spec(&self) -> Result<WavSpec> {
+        let sample_format = match self.sample_type {
+            Sample::Unsigned8bits(_)
+            | Sample::Signed16bits(_)
+            | Sample::Signed24bits(_)
+            | Sample::Signed32bits(_) => SampleFormat::Int,
+            Sample::Float(_) => SampleFormat::Float,
+            ref s => return Err(PCMError::UnsupportedSampleType(s.clone())),
+        };
+        Ok(WavSpec {
+            channels: self.nb_channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.sample_type.get_binary_size(),
+            sample_format,
+        })
+    }
+    /// Creates parameters from a hound spec, 8 bits integers being Unsigned 8 bits as in Wave files
+    pub fn from_hound_spec(spec: WavSpec) -> Result<PCMParameters> {
+        let sample_type = match (spec.sample_format, spec.bits_per_sample) {
+            (SampleFormat::Int, 8) => Sample::Unsigned8bits(0),
+            (SampleFormat::Int, 16) => Sample::Signed16bits(0),
+            (SampleFormat::Int, 24) => Sample::Signed24bits(I24 { value: 0 }),
+            (SampleFormat::Int, 32) => Sample::Signed32bits(0),
+            (SampleFormat::Float, 32) => Sample::Float(0f32),
+            (_, b) => return Err(PCMError::UnknownBitsPerSample(b)),
+        };
+        PCMParameters::new(spec.sample_rate, spec.channels, sample_type)
+    }
+}
+
+impl PCM {
+    /// Builds a PCM from interleaved samples read by hound, such as the ones given by WavReader::samples
+    pub fn from_hound_samples<S: HoundSample, I: IntoIterator<Item = S>>(
+        spec: WavSpec,
+        samples: I,
+    ) -> Result<PCM> {
+        let parameters = PCMParameters::from_hound_spec(spec)?;
+        let nb_channels = parameters.nb_channels as usize;
+        let mut frames = Vec::new();
+        let mut frame_samples = Vec::with_capacity(nb_channels);
+        for sample in samples {
+            frame_samples.push(sample.to_sample(&parameters.sample_type)?);
+            if frame_samples.len() == nb_channels {
+                frames.push(Frame {
+                    samples: mem::replace(&mut frame_samples, Vec::with_capacity(nb_channels)),
+                });
+            }
+        }
+        if !frame_samples.is_empty() {
+            return Err(PCMError::PartialFrame(
+                frame_samples.len() * parameters.bytes_per_sample() as usize,
+            ));
+        }
+        Ok(PCM {
+            parameters,
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use error::PCMError;
+    use hound::{SampleFormat, WavSpec};
+    use {PCMParameters, Sample, PCM};
+    #[test]
+    fn spec_round_trip() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 24,
+            sample_format: SampleFormat::Int,
+        };
+        let parameters = PCMParameters::from_hound_spec(spec).unwrap();
+        assert_eq!(parameters.bytes_per_frame(), 6);
+        assert_eq!(parameters.to_hound_spec().unwrap(), spec);
+        let double = PCMParameters::new(44100, 2, Sample::DoubleFloat(0f64)).unwrap();
+        assert!(double.to_hound_spec().is_err());
+    }
+    #[test]
+    fn from_samples() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 8,
+            sample_format: SampleFormat::Int,
+        };
+        let pcm = PCM::from_hound_samples(spec, vec![-128i8, 0, 127, 1]).unwrap();
+        assert_eq!(pcm.frames.len(), 2);
+        assert_eq!(
+            pcm.frames[0].samples,
+            vec![Sample::Unsigned8bits(0), Sample::Unsigned8bits(128)]
+        );
+        assert_eq!(pcm.frames[1].samples[0], Sample::Unsigned8bits(255));
+        assert!(PCM::from_hound_samples(spec, vec![0i8; 3]).is_err());
+        match PCM::from_hound_samples(spec, vec![0i32, 128]) {
+            Err(PCMError::SampleOutOfRange(128, Sample::Unsigned8bits(_))) => {}
+            _ => panic!(),
+        }
+        let spec_24 = WavSpec {
+            bits_per_sample: 24,
+            ..spec
+        };
+        assert!(PCM::from_hound_samples(spec_24, vec![-0x80_0000i32, 0x7F_FFFF]).is_ok());
+        assert!(PCM::from_hound_samples(spec_24, vec![0i32, 0x80_0000]).is_err());
+        assert!(PCM::from_hound_samples(spec, vec![0f32; 2]).is_err());
+    }
+}
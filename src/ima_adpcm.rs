@@ -0,0 +1,263 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use ez_io::WriteE;
+#[cfg(feature = "std")]
+use std::io::Write;
+use {Frame, PCMError, Result, Sample};
+
+/// Size of the quantization steps, indexed by the step index
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408,
+    449, 494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066,
+    2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630,
+    9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+    32767,
+];
+
+/// How much the step index changes after each nibble
+const INDEX_TABLE: [i32; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+/// Size in bytes of a block for a single channel when nothing else is specified
+pub const DEFAULT_BLOCK_SIZE_PER_CHANNEL: u16 = 256;
+
+/// Encoder/Decoder state for a single channel
+#[derive(Clone, Default)]
+struct ChannelState {
+    predictor: i32,
+    step_index: i32,
+}
+
+impl ChannelState {
+    #[cfg(feature = "std")]
+    /// Quantizes a sample into a nibble and updates the state like the decoder would
+    fn encode(&mut self, sample: i16) -> u8 {
+        let mut step = STEP_TABLE[self.step_index as usize];
+        let mut diff = i32::from(sample) - self.predictor;
+        let mut nibble = 0u8;
+        if diff < 0 {
+            nibble = 8;
+            diff = -diff;
+        }
+        let mut vpdiff = step >> 3;
+        if diff >= step {
+            nibble |= 4;
+            diff -= step;
+            vpdiff += step;
+        }
+        step >>= 1;
+        if diff >= step {
+            nibble |= 2;
+            diff -= step;
+            vpdiff += step;
+        }
+        step >>= 1;
+        if diff >= step {
+            nibble |= 1;
+            vpdiff += step;
+        }
+        self.update(nibble, vpdiff);
+        nibble
+    }
+    /// Expands a nibble into a sample
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = STEP_TABLE[self.step_index as usize];
+        let mut vpdiff = step >> 3;
+        if nibble & 4 != 0 {
+            vpdiff += step;
+        }
+        if nibble & 2 != 0 {
+            vpdiff += step >> 1;
+        }
+        if nibble & 1 != 0 {
+            vpdiff += step >> 2;
+        }
+        self.update(nibble, vpdiff);
+        self.predictor as i16
+    }
+    /// Applies a nibble to the predictor and the step index
+    fn update(&mut self, nibble: u8, vpdiff: i32) {
+        if nibble & 8 != 0 {
+            self.predictor -= vpdiff;
+        } else {
+            self.predictor += vpdiff;
+        }
+        self.predictor = self
+            .predictor
+            .max(i32::from(<i16>::min_value()))
+            .min(i32::from(<i16>::max_value()));
+        self.step_index = (self.step_index + INDEX_TABLE[nibble as usize]).max(0).min(88);
+    }
+}
+
+/// Returns the block align used when nothing else is specified, saturating at u16::MAX for channel counts that big
+pub fn default_block_align(nb_channels: u16) -> u16 {
+    DEFAULT_BLOCK_SIZE_PER_CHANNEL.saturating_mul(nb_channels)
+}
+
+/// Checks that a block align leaves room for the block headers and holds whole groups of 4 bytes per channel
+pub fn check_block_align(block_align: u16, nb_channels: u16) -> Result<()> {
+    let header_size = 4 * u32::from(nb_channels);
+    let block_align_u32 = u32::from(block_align);
+    if nb_channels == 0
+        || block_align_u32 <= header_size
+        || (block_align_u32 - header_size) % header_size != 0
+    {
+        return Err(PCMError::InvalidBlockAlign(block_align));
+    }
+    Ok(())
+}
+
+/// Returns how many frames are stored in a block, including the one in the block header
+pub fn samples_per_block(block_align: u16, nb_channels: u16) -> Result<u16> {
+    check_block_align(block_align, nb_channels)?;
+    Ok(
+        ((u32::from(block_align) - 4 * u32::from(nb_channels)) * 2 / u32::from(nb_channels) + 1)
+            as u16,
+    )
+}
+
+/// Returns the size in bytes of the encoded data for a given number of frames
+pub fn encoded_size(nb_frames: usize, nb_channels: u16, block_align: u16) -> Result<usize> {
+    let samples_per_block = samples_per_block(block_align, nb_channels)? as usize;
+    Ok(nb_frames.div_ceil(samples_per_block) * block_align as usize)
+}
+
+#[cfg(feature = "std")]
+/// Encodes frames of Signed 16 bits samples into IMA ADPCM blocks. The last block is padded by repeating the last frame.
+pub fn encode<W: Write>(
+    writer: &mut W,
+    frames: &[Frame],
+    nb_channels: u16,
+    block_align: u16,
+) -> Result<()> {
+    let samples_per_block = samples_per_block(block_align, nb_channels)? as usize;
+    let mut states = vec![ChannelState::default(); nb_channels as usize];
+    for block in frames.chunks(samples_per_block) {
+        // Block header, the first sample is stored as is
+        for (channel, state) in states.iter_mut().enumerate() {
+            let first = get_sample(&block[0], channel)?;
+            state.predictor = i32::from(first);
+            writer.write_le_to_i16(first)?;
+            writer.write_to_u8(state.step_index as u8)?;
+            writer.write_to_u8(0)?;
+        }
+        let mut nibbles = vec![Vec::with_capacity(samples_per_block - 1); nb_channels as usize];
+        for frame_id in 1..samples_per_block {
+            let frame = block.get(frame_id).unwrap_or(&block[block.len() - 1]);
+            for (channel, state) in states.iter_mut().enumerate() {
+                nibbles[channel].push(state.encode(get_sample(frame, channel)?));
+            }
+        }
+        // Channels are interleaved every 4 bytes, lower nibble first
+        for group in 0..(samples_per_block - 1) / 8 {
+            for channel_nibbles in &nibbles {
+                for pair in channel_nibbles[group * 8..(group + 1) * 8].chunks(2) {
+                    writer.write_to_u8(pair[0] | (pair[1] << 4))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes IMA ADPCM blocks into frames of Signed 16 bits samples
+pub fn decode(data: &[u8], nb_channels: u16, block_align: u16) -> Result<Vec<Frame>> {
+    check_block_align(block_align, nb_channels)?;
+    let nb_channels = nb_channels as usize;
+    let mut frames = Vec::new();
+    for block in data.chunks(block_align as usize) {
+        if block.len() < 4 * nb_channels {
+            break;
+        }
+        let mut states = Vec::with_capacity(nb_channels);
+        let mut samples = Vec::with_capacity(nb_channels);
+        for header in block.chunks(4).take(nb_channels) {
+            let predictor = i16::from(header[0]) | (i16::from(header[1]) << 8);
+            states.push(ChannelState {
+                predictor: i32::from(predictor),
+                step_index: i32::from(header[2]).min(88),
+            });
+            samples.push(Sample::Signed16bits(predictor));
+        }
+        frames.push(Frame { samples });
+        let mut channels = vec![Vec::new(); nb_channels];
+        for (group_id, group) in block[4 * nb_channels..].chunks(4).enumerate() {
+            let channel = group_id % nb_channels;
+            for byte in group {
+                channels[channel].push(states[channel].decode(byte & 0x0F));
+                channels[channel].push(states[channel].decode(byte >> 4));
+            }
+        }
+        let nb_frames = channels.iter().map(|c| c.len()).min().unwrap_or(0);
+        for frame_id in 0..nb_frames {
+            frames.push(Frame {
+                samples: channels
+                    .iter()
+                    .map(|c| Sample::Signed16bits(c[frame_id]))
+                    .collect(),
+            });
+        }
+    }
+    Ok(frames)
+}
+
+#[cfg(feature = "std")]
+/// Returns the value of a Signed 16 bits sample in a frame
+fn get_sample(frame: &Frame, channel: usize) -> Result<i16> {
+    match frame.samples[channel] {
+        Sample::Signed16bits(v) => Ok(v),
+        ref s => Err(PCMError::UnsupportedSampleType(s.clone())),
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    #[test]
+    fn encode_then_decode() {
+        let frames: Vec<Frame> = (0..2000)
+            .map(|i| {
+                let t = f64::from(i) / 44100f64;
+                Frame {
+                    samples: vec![
+                        Sample::Signed16bits(((t * 440f64 * 6.283185).sin() * 12000f64) as i16),
+                        Sample::Signed16bits(((t * 220f64 * 6.283185).sin() * 6000f64) as i16),
+                    ],
+                }
+            })
+            .collect();
+        let block_align = default_block_align(2);
+        let mut data = Vec::new();
+        encode(&mut data, &frames, 2, block_align).unwrap();
+        assert_eq!(data.len(), encoded_size(frames.len(), 2, block_align).unwrap());
+        let decoded = decode(&data, 2, block_align).unwrap();
+        assert!(decoded.len() >= frames.len());
+        // Let the step size adapt before checking the error
+        for (original, decoded) in frames.iter().zip(decoded.iter()).skip(32) {
+            for (o, d) in original.samples.iter().zip(decoded.samples.iter()) {
+                match (o, d) {
+                    (Sample::Signed16bits(o), Sample::Signed16bits(d)) => {
+                        assert!((i32::from(*o) - i32::from(*d)).abs() < 512)
+                    }
+                    _ => panic!(),
+                }
+            }
+        }
+    }
+    #[test]
+    fn block_align() {
+        assert!(check_block_align(default_block_align(2), 2).is_ok());
+        assert!(check_block_align(36, 1).is_ok());
+        assert!(check_block_align(4, 1).is_err());
+        assert!(check_block_align(38, 2).is_err());
+        assert_eq!(default_block_align(u16::MAX), u16::MAX);
+        assert!(samples_per_block(default_block_align(u16::MAX), u16::MAX).is_err());
+        assert_eq!(samples_per_block(36, 1).unwrap(), 65);
+        assert!(samples_per_block(4, 2).is_err());
+        assert!(encoded_size(10, 0, 256).is_err());
+        assert!(decode(&[0; 8], 0, 8).is_err());
+        assert!(decode(&[0; 8], 1, 0).is_err());
+    }
+}
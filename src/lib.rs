@@ -1,53 +1,169 @@
 //! A crate for manipulating PCM-related data in Rust.
 //!
 //! This crate currently allows for Importing and Writing Wave files with limited support for types.
+//!
+//! Without the default `std` feature, only the types, the conversions between samples and the channel operations are available, using `alloc`.
+//!
+//! The `flac` feature allows importing FLAC files.
+//!
+//! The `hound` feature allows converting parameters and samples from the `hound` crate.
+//!
+//! The `rayon` feature allows decoding Wave files on all cores, it enables `std`.
+//!
+//! The `serde` feature allows serializing the parameters and the loop information, sample types being stored by name.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "std")]
 extern crate ez_io;
+#[cfg(feature = "std")]
 extern crate magic_number;
+#[cfg(feature = "flac")]
+extern crate claxon;
+#[cfg(feature = "hound")]
+extern crate hound;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 /// Contains the errors for this library
 pub mod error;
 /// Contains structs for different types of samples for use in PCM data
 pub mod sample_types;
 /// Functions for Importing and Exporting Wave files
+#[cfg(feature = "std")]
 pub mod wave;
+/// Functions for Importing and Exporting AIFF files
+#[cfg(feature = "std")]
+pub mod aiff;
 /// Conversions between different Samples
 pub mod conversion;
+/// Dither added when reducing the bit depth of samples
+pub mod dither;
+/// Encoding and Decoding of IMA ADPCM data
+pub mod ima_adpcm;
+/// Encoding and Decoding of A-law and µ-law (G.711) data
+pub mod g711;
+/// Decoding of Microsoft ADPCM data
+#[cfg(feature = "std")]
+pub mod microsoft_adpcm;
+/// Changing the sample rate of PCM data
+#[cfg(feature = "std")]
+pub mod resample;
+/// Splitting and merging channels
+pub mod channels;
+/// Effects modifying the samples of PCM data
+#[cfg(feature = "std")]
+pub mod effects;
+/// Biquad filters for PCM data
+#[cfg(feature = "std")]
+pub mod filter;
+/// Measurements of levels in PCM data
+#[cfg(feature = "std")]
+pub mod analysis;
+/// Cutting and joining PCM data
+#[cfg(feature = "std")]
+pub mod editing;
+/// Building PCM data step by step
+pub mod builder;
+/// Generation of test signals
+#[cfg(feature = "std")]
+pub mod generator;
+/// Importing FLAC files
+#[cfg(feature = "flac")]
+pub mod flac;
+/// Conversions from and to the types of the hound crate
+#[cfg(feature = "hound")]
+pub mod hound_compat;
+/// Serialization of sample types by name
+#[cfg(feature = "serde")]
+mod serialization;
 
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem;
+use core::time::Duration;
 use error::PCMError;
-use ez_io::WriteE;
-use sample_types::{I24, ImaADPCM, MicrosoftADPCM};
-use std::fmt;
-use std::io::{Seek, Write};
-use std::time::Duration;
+#[cfg(feature = "std")]
+use ez_io::ReadE;
+use sample_types::{ALaw, I24, ImaADPCM, MicrosoftADPCM, MuLaw};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::io::{Cursor, Read, Seek, Write};
 
 /// The main result type used everywhere in this Library
-type Result<T> = std::result::Result<T, PCMError>;
+type Result<T> = core::result::Result<T, PCMError>;
+
+/// Number of frames encoded before each write by write_interleaved
+#[cfg(feature = "std")]
+const FRAMES_PER_BATCH: usize = 4096;
+
+/// Biggest amount of memory allocated before reading data whose size comes from a file
+#[cfg(feature = "std")]
+const MAX_PREALLOCATION: u64 = 1 << 20;
+
+/// Reads a number of bytes, memory being allocated as the data comes in so that a wrong size cannot exhaust it
+#[cfg(feature = "std")]
+fn read_bytes<R: Read>(reader: &mut R, size: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size.min(MAX_PREALLOCATION) as usize);
+    reader.take(size).read_to_end(&mut data)?;
+    if (data.len() as u64) < size {
+        return Err(PCMError::TruncatedData {
+            expected: size,
+            actual: data.len() as u64,
+        });
+    }
+    Ok(data)
+}
 
 /// Represents PCM data.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct PCM {
     /// Parameters for this signal
     pub parameters: PCMParameters,
     /// Loop information if any
     pub loop_info: Option<Vec<LoopInfo>>,
+    /// Cue points placed in the stream
+    pub markers: Vec<CuePoint>,
+    /// Broadcast metadata if any
+    pub broadcast_info: Option<BroadcastInfo>,
+    /// Text tags, such as INAM for the title, IART for the artist or ICMT for a comment
+    pub tags: BTreeMap<[u8; 4], String>,
     /// Frames that composes the stream
     pub frames: Vec<Frame>,
 }
 
 /// Parameters for PCM signal
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PCMParameters {
     /// Number of samples per second
     pub sample_rate: u32,
     /// Number of samples per frame
     pub nb_channels: u16,
     /// Sample type to use in frames
+    #[cfg_attr(feature = "serde", serde(with = "serialization"))]
     pub sample_type: Sample,
+    /// Speaker positions of the channels, as found in a WAVE_FORMAT_EXTENSIBLE header
+    pub channel_mask: Option<u32>,
 }
 
 /// Information about Looping in PCM data
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LoopInfo {
     /// Where does the loop start in frame count
     pub loop_start: u64,
@@ -55,14 +171,116 @@ pub struct LoopInfo {
     pub loop_end: u64,
 }
 
+/// A named position in PCM data
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CuePoint {
+    /// Unique identifier of this cue point
+    pub id: u32,
+    /// Position of this cue point in frame count
+    pub position: u64,
+    /// Name of this cue point if any
+    pub label: Option<String>,
+}
+
+/// Broadcast metadata, as found in the bext chunk of a Broadcast Wave file
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BroadcastInfo {
+    /// Description of the sound, up to 256 characters
+    pub description: String,
+    /// Name of the originator, up to 32 characters
+    pub originator: String,
+    /// Reference given by the originator, up to 32 characters
+    pub originator_reference: String,
+    /// Date of creation, formatted as yyyy-mm-dd
+    pub origination_date: String,
+    /// Time of creation, formatted as hh-mm-ss
+    pub origination_time: String,
+    /// Number of samples since midnight at the first frame, used to sync with timecode
+    pub time_reference: u64,
+    /// Version of the bext chunk
+    pub version: u16,
+    /// SMPTE Unique Material Identifier, only in version 1 and above
+    pub umid: [u8; 64],
+    /// Integrated loudness, loudness range, maximum true peak, maximum momentary and maximum short-term loudness, in hundredths, only in version 2
+    pub loudness: [i16; 5],
+    /// History of the coding processes applied to the sound
+    pub coding_history: String,
+}
+
+/// Position of the speaker a channel is meant to be played on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpeakerPosition {
+    /// Front Left
+    FrontLeft,
+    /// Front Right
+    FrontRight,
+    /// Front Center
+    FrontCenter,
+    /// Low Frequency Effects (LFE)
+    LowFrequency,
+    /// Back Left
+    BackLeft,
+    /// Back Right
+    BackRight,
+    /// Front Left of Center
+    FrontLeftOfCenter,
+    /// Front Right of Center
+    FrontRightOfCenter,
+    /// Back Center
+    BackCenter,
+    /// Side Left
+    SideLeft,
+    /// Side Right
+    SideRight,
+    /// Top Center
+    TopCenter,
+    /// Top Front Left
+    TopFrontLeft,
+    /// Top Front Center
+    TopFrontCenter,
+    /// Top Front Right
+    TopFrontRight,
+    /// Top Back Left
+    TopBackLeft,
+    /// Top Back Center
+    TopBackCenter,
+    /// Top Back Right
+    TopBackRight,
+    /// Channel not described by the channel mask
+    Unknown,
+}
+
+/// Speaker positions in the same order as the bits of a channel mask
+const SPEAKER_POSITIONS: [SpeakerPosition; 18] = [
+    SpeakerPosition::FrontLeft,
+    SpeakerPosition::FrontRight,
+    SpeakerPosition::FrontCenter,
+    SpeakerPosition::LowFrequency,
+    SpeakerPosition::BackLeft,
+    SpeakerPosition::BackRight,
+    SpeakerPosition::FrontLeftOfCenter,
+    SpeakerPosition::FrontRightOfCenter,
+    SpeakerPosition::BackCenter,
+    SpeakerPosition::SideLeft,
+    SpeakerPosition::SideRight,
+    SpeakerPosition::TopCenter,
+    SpeakerPosition::TopFrontLeft,
+    SpeakerPosition::TopFrontCenter,
+    SpeakerPosition::TopFrontRight,
+    SpeakerPosition::TopBackLeft,
+    SpeakerPosition::TopBackCenter,
+    SpeakerPosition::TopBackRight,
+];
+
 /// Contains a sample for each channel in the stream
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Frame {
     /// Samples for all the different channels
     pub samples: Vec<Sample>,
 }
 
-/// A value representing a level in the signal
+/// A value representing a level in the signal.
+/// Float samples are compared and hashed by their bits, so NaN equals itself while 0.0 and -0.0 differ.
 #[derive(Clone, Debug)]
 pub enum Sample {
     /// One unsigned byte
@@ -77,42 +295,374 @@ pub enum Sample {
     ImaADPCM(ImaADPCM),
     /// Half byte Microsoft ADPCM
     MicrosoftADPCM(MicrosoftADPCM),
+    /// One byte A-law
+    ALaw(ALaw),
+    /// One byte µ-law
+    MuLaw(MuLaw),
     /// Four bytes float
     Float(f32),
     /// Eight bytes float
     DoubleFloat(f64),
 }
 
+/// Byte order used when reading or writing samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endianness {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
 impl PCM {
-    /// Writes all samples directly to a writer
+    /// Creates a PCM without any frame or metadata
+    pub fn empty(parameters: PCMParameters) -> PCM {
+        PCM {
+            parameters,
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: Vec::new(),
+        }
+    }
+    #[cfg(feature = "std")]
+    /// Writes all samples directly to a writer, in little-endian
     pub fn export_raw_file<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
-        for frame in &self.frames {
-            for sample in &frame.samples {
-                match sample {
-                    Sample::Unsigned8bits(s) => writer.write_to_u8(s.clone())?,
-                    Sample::Signed16bits(s) => writer.write_le_to_i16(s.clone())?, // Todo: Allow for choosing endianness
-                    Sample::Float(s) => writer.write_le_to_f32(s.clone())?,
-                    Sample::DoubleFloat(s) => writer.write_le_to_f64(s.clone())?,
-                    _ => unimplemented!("Can only write u8s, u16s, f32s and f64s for now"),
+        self.export_raw_file_endian(writer, Endianness::Little)
+    }
+    #[cfg(feature = "std")]
+    /// Writes all samples directly to a writer, in the chosen byte order
+    pub fn export_raw_file_endian<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endianness,
+    ) -> Result<()> {
+        self.write_interleaved(writer, endian)
+    }
+    #[cfg(feature = "std")]
+    /// Writes all samples to a writer in the chosen byte order, frames being encoded in batches.
+    /// Samples must all be of the type given in the parameters.
+    pub fn write_interleaved<W: Write>(&self, writer: &mut W, endian: Endianness) -> Result<()> {
+        let little = endian == Endianness::Little;
+        match self.parameters.sample_type {
+            Sample::ImaADPCM(_) => {
+                // Blocks span several frames so they are all checked before encoding
+                self.validate()?;
+                ima_adpcm::encode(
+                    writer,
+                    &self.frames,
+                    self.parameters.nb_channels,
+                    ima_adpcm::default_block_align(self.parameters.nb_channels),
+                )
+            }
+            Sample::ALaw(_) | Sample::MuLaw(_) => {
+                g711::encode(writer, &self.frames, &self.parameters.sample_type)
+            }
+            Sample::Unsigned8bits(_) => self.write_batched(writer, 1, |sample, bytes| match sample {
+                Sample::Unsigned8bits(s) => {
+                    bytes.push(*s);
+                    true
+                }
+                _ => false,
+            }),
+            Sample::Signed16bits(_) => self.write_batched(writer, 2, |sample, bytes| match sample {
+                Sample::Signed16bits(s) if little => {
+                    bytes.extend_from_slice(&s.to_le_bytes());
+                    true
+                }
+                Sample::Signed16bits(s) => {
+                    bytes.extend_from_slice(&s.to_be_bytes());
+                    true
+                }
+                _ => false,
+            }),
+            Sample::Signed24bits(_) => self.write_batched(writer, 3, |sample, bytes| match sample {
+                Sample::Signed24bits(s) if little => {
+                    bytes.extend_from_slice(&s.value.to_le_bytes()[..3]);
+                    true
+                }
+                Sample::Signed24bits(s) => {
+                    bytes.extend_from_slice(&s.value.to_be_bytes()[1..]);
+                    true
+                }
+                _ => false,
+            }),
+            Sample::Signed32bits(_) => self.write_batched(writer, 4, |sample, bytes| match sample {
+                Sample::Signed32bits(s) if little => {
+                    bytes.extend_from_slice(&s.to_le_bytes());
+                    true
+                }
+                Sample::Signed32bits(s) => {
+                    bytes.extend_from_slice(&s.to_be_bytes());
+                    true
+                }
+                _ => false,
+            }),
+            Sample::Float(_) => self.write_batched(writer, 4, |sample, bytes| match sample {
+                Sample::Float(s) if little => {
+                    bytes.extend_from_slice(&s.to_le_bytes());
+                    true
+                }
+                Sample::Float(s) => {
+                    bytes.extend_from_slice(&s.to_be_bytes());
+                    true
+                }
+                _ => false,
+            }),
+            Sample::DoubleFloat(_) => self.write_batched(writer, 8, |sample, bytes| match sample {
+                Sample::DoubleFloat(s) if little => {
+                    bytes.extend_from_slice(&s.to_le_bytes());
+                    true
+                }
+                Sample::DoubleFloat(s) => {
+                    bytes.extend_from_slice(&s.to_be_bytes());
+                    true
+                }
+                _ => false,
+            }),
+            ref s => Err(PCMError::UnsupportedSampleType(s.clone())),
+        }
+    }
+    #[cfg(feature = "std")]
+    /// Encodes frames into a buffer and writes it once full, encode returning false for a sample of the wrong type
+    fn write_batched<W: Write, F: Fn(&Sample, &mut Vec<u8>) -> bool>(
+        &self,
+        writer: &mut W,
+        sample_size: usize,
+        encode: F,
+    ) -> Result<()> {
+        let nb_channels = self.parameters.nb_channels as usize;
+        let mut buffer = Vec::with_capacity(sample_size * nb_channels * FRAMES_PER_BATCH);
+        for (batch_id, batch) in self.frames.chunks(FRAMES_PER_BATCH).enumerate() {
+            buffer.clear();
+            for (frame_id, frame) in batch.iter().enumerate() {
+                if frame.samples.len() != nb_channels {
+                    return Err(PCMError::WrongNumberOfSamples(
+                        batch_id * FRAMES_PER_BATCH + frame_id,
+                        frame.samples.len(),
+                    ));
+                }
+                for sample in &frame.samples {
+                    if !encode(sample, &mut buffer) {
+                        return Err(PCMError::WrongSampleType(
+                            batch_id * FRAMES_PER_BATCH + frame_id,
+                            sample.clone(),
+                        ));
+                    }
                 }
             }
+            writer.write_all(&buffer)?;
         }
         Ok(())
     }
-    /// Returns the size of the raw stream in bytes
+    #[cfg(feature = "std")]
+    /// Reads headerless little-endian samples until the end of the reader, Microsoft ADPCM is not supported
+    pub fn import_raw_file<R: Read>(reader: &mut R, parameters: PCMParameters) -> Result<PCM> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let frames = match parameters.sample_type {
+            Sample::ImaADPCM(_) => ima_adpcm::decode(
+                &data,
+                parameters.nb_channels,
+                ima_adpcm::default_block_align(parameters.nb_channels),
+            )?,
+            Sample::MicrosoftADPCM(_) => {
+                return Err(PCMError::UnsupportedSampleType(parameters.sample_type))
+            }
+            _ => {
+                let block_size = parameters.frame_size();
+                if block_size == 0 {
+                    Vec::new()
+                } else {
+                    if data.len() % block_size != 0 {
+                        return Err(PCMError::PartialFrame(data.len() % block_size));
+                    }
+                    let nb_frames = data.len() / block_size;
+                    let mut data_reader = Cursor::new(data);
+                    let mut frames = Vec::with_capacity(nb_frames);
+                    for _ in 0..nb_frames {
+                        let mut samples = Vec::with_capacity(parameters.nb_channels as usize);
+                        for _ in 0..parameters.nb_channels {
+                            samples.push(
+                                parameters
+                                    .sample_type
+                                    .read_raw(&mut data_reader, Endianness::Little)?,
+                            );
+                        }
+                        frames.push(Frame { samples });
+                    }
+                    frames
+                }
+            }
+        };
+        Ok(PCM {
+            parameters,
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames,
+        })
+    }
+    /// Checks that every frame has one sample per channel, all of the type given in the parameters
+    pub fn validate(&self) -> Result<()> {
+        // ADPCM data is kept decoded
+        let expected_type = match self.parameters.sample_type {
+            Sample::ImaADPCM(_)
+            | Sample::MicrosoftADPCM(_)
+            | Sample::ALaw(_)
+            | Sample::MuLaw(_) => Sample::Signed16bits(0),
+            ref s => s.clone(),
+        };
+        for (frame_id, frame) in self.frames.iter().enumerate() {
+            if frame.samples.len() != self.parameters.nb_channels as usize {
+                return Err(PCMError::WrongNumberOfSamples(frame_id, frame.samples.len()));
+            }
+            if let Some(s) = frame.samples.iter().find(|s| !s.is_same_type(&expected_type)) {
+                return Err(PCMError::WrongSampleType(frame_id, s.clone()));
+            }
+        }
+        Ok(())
+    }
+    /// Returns a hash of the parameters and the frames, loops and cue points are ignored.
+    /// It stays the same between runs on the same platform, float samples are hashed by their bits.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = ContentHasher {
+            state: 0xCBF2_9CE4_8422_2325,
+        };
+        self.parameters.hash(&mut hasher);
+        self.frames.hash(&mut hasher);
+        hasher.finish()
+    }
+    /// Returns the size of the raw stream in bytes, 0 for IMA ADPCM with more channels than a block can hold
     pub fn get_audio_size(&self) -> usize {
+        if let Sample::ImaADPCM(_) = self.parameters.sample_type {
+            return ima_adpcm::encoded_size(
+                self.frames.len(),
+                self.parameters.nb_channels,
+                ima_adpcm::default_block_align(self.parameters.nb_channels),
+            )
+            .unwrap_or(0);
+        }
+        if let Sample::ALaw(_) | Sample::MuLaw(_) = self.parameters.sample_type {
+            return self.frames.len() * self.parameters.frame_size();
+        }
         self.frames.len() * match self.frames.get(0) {
             Some(f) => f.get_audio_size(),
             None => 0,
         }
     }
-    /// Get the duration of the signal
+    /// Get the duration of the signal, zero for a sample rate of 0
     pub fn get_audio_duration(&self) -> Duration {
-        let duration_float = (self.frames.len() as f64) / f64::from(self.parameters.sample_rate);
-        Duration::new(
-            duration_float.round() as u64,
-            (duration_float.fract() * 10f64.powi(9)) as u32,
-        )
+        if self.parameters.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64((self.frames.len() as f64) / f64::from(self.parameters.sample_rate))
+    }
+    /// Returns the index of the frame playing at a time, which is the last one starting at or before it.
+    /// Times past the end give indexes past the last frame.
+    pub fn frame_index_at_time(&self, time: Duration) -> u64 {
+        (time.as_nanos() * u128::from(self.parameters.sample_rate) / 1_000_000_000) as u64
+    }
+    /// Returns the frame playing at a time, None if the time is past the end of the signal
+    pub fn frame_at_time(&self, time: Duration) -> Option<&Frame> {
+        self.frames.get(self.frame_index_at_time(time) as usize)
+    }
+    /// Returns the number of frames
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+    /// Returns the number of samples, counting every channel
+    pub fn sample_count(&self) -> usize {
+        self.frames.len() * self.parameters.nb_channels as usize
+    }
+}
+
+impl Default for PCMParameters {
+    /// Stereo Signed 16 bits at 44100 Hz, as on a CD
+    fn default() -> PCMParameters {
+        PCMParameters {
+            sample_rate: 44100,
+            nb_channels: 2,
+            sample_type: Sample::Signed16bits(0),
+            channel_mask: None,
+        }
+    }
+}
+
+impl PCMParameters {
+    /// Creates parameters without a channel mask, rejecting a sample rate of 0 and 0 channels
+    pub fn new(sample_rate: u32, nb_channels: u16, sample_type: Sample) -> Result<PCMParameters> {
+        if sample_rate == 0 {
+            return Err(PCMError::InvalidParameters("a sample rate of 0"));
+        }
+        if nb_channels == 0 {
+            return Err(PCMError::InvalidParameters("0 channels"));
+        }
+        Ok(PCMParameters {
+            sample_rate,
+            nb_channels,
+            sample_type,
+            channel_mask: None,
+        })
+    }
+    /// Returns how big a sample is in bytes, 0 for ADPCM which packs two samples per byte
+    pub fn bytes_per_sample(&self) -> u16 {
+        self.sample_type.get_binary_size() / 8
+    }
+    /// Returns how big a frame is in bytes, 0 for ADPCM, saturating at u16::MAX for frames that big
+    pub fn bytes_per_frame(&self) -> u16 {
+        self.bytes_per_sample().saturating_mul(self.nb_channels)
+    }
+    /// Returns how many bytes of audio data there are per second, 0 for ADPCM, saturating at u32::MAX
+    pub fn byte_rate(&self) -> u32 {
+        self.sample_rate.saturating_mul(u32::from(self.bytes_per_frame()))
+    }
+    /// Returns how big a frame is in bytes without overflowing, 0 for ADPCM
+    pub(crate) fn frame_size(&self) -> usize {
+        self.bytes_per_sample() as usize * self.nb_channels as usize
+    }
+    /// Returns the channel mask, or the usual one for this number of channels if there is none
+    pub fn get_channel_mask(&self) -> u32 {
+        match self.channel_mask {
+            Some(m) => m,
+            None => match self.nb_channels {
+                1 => 0x4,   // Mono
+                2 => 0x3,   // Stereo
+                4 => 0x33,  // Quad
+                6 => 0x3F,  // 5.1
+                8 => 0x63F, // 7.1
+                _ => 0,
+            },
+        }
+    }
+    #[cfg(feature = "std")]
+    /// Checks that PCM data with both parameters can be put together
+    fn check_compatible(&self, other: &PCMParameters) -> Result<()> {
+        if self.sample_rate != other.sample_rate {
+            return Err(PCMError::IncompatibleParameters("sample rate"));
+        }
+        if self.nb_channels != other.nb_channels {
+            return Err(PCMError::IncompatibleParameters("number of channels"));
+        }
+        if !self.sample_type.is_same_type(&other.sample_type) {
+            return Err(PCMError::IncompatibleParameters("sample type"));
+        }
+        Ok(())
+    }
+    /// Returns the speaker position of every channel
+    pub fn channel_positions(&self) -> Vec<SpeakerPosition> {
+        let mask = self.get_channel_mask();
+        let mut positions: Vec<SpeakerPosition> = SPEAKER_POSITIONS
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| mask & (1 << bit) != 0)
+            .map(|(_, p)| *p)
+            .take(self.nb_channels as usize)
+            .collect();
+        positions.resize(self.nb_channels as usize, SpeakerPosition::Unknown);
+        positions
     }
 }
 
@@ -124,9 +674,68 @@ impl Frame {
             None => 0,
         }
     }
+    /// Returns the sample of a channel, if this frame has it
+    pub fn get(&self, channel: u16) -> Option<&Sample> {
+        self.samples.get(channel as usize)
+    }
+    /// Replaces the sample of a channel by one of the same type
+    pub fn set(&mut self, channel: u16, s: Sample) -> Result<()> {
+        match self.samples.get_mut(channel as usize) {
+            Some(sample) if sample.is_same_type(&s) => {
+                *sample = s;
+                Ok(())
+            }
+            Some(_) => Err(PCMError::UnsupportedSampleType(s)),
+            None => Err(PCMError::ChannelOutOfRange(channel)),
+        }
+    }
 }
 
 impl Sample {
+    #[cfg(feature = "std")]
+    /// Reads a sample of the same type as this one
+    fn read_raw<R: Read>(&self, reader: &mut R, endian: Endianness) -> Result<Sample> {
+        let little = endian == Endianness::Little;
+        Ok(match self {
+            Sample::Unsigned8bits(_) => Sample::Unsigned8bits(reader.read_to_u8()?),
+            Sample::Signed16bits(_) if little => Sample::Signed16bits(reader.read_le_to_i16()?),
+            Sample::Signed16bits(_) => Sample::Signed16bits(reader.read_be_to_i16()?),
+            Sample::Signed24bits(_) => {
+                let mut bytes = [0u8; 3];
+                reader.read_exact(&mut bytes)?;
+                if !little {
+                    bytes.reverse();
+                }
+                Sample::Signed24bits(I24 {
+                    value: (i32::from(bytes[0]) << 8
+                        | i32::from(bytes[1]) << 16
+                        | i32::from(bytes[2]) << 24)
+                        >> 8,
+                })
+            }
+            Sample::Signed32bits(_) if little => Sample::Signed32bits(reader.read_le_to_i32()?),
+            Sample::Signed32bits(_) => Sample::Signed32bits(reader.read_be_to_i32()?),
+            Sample::Float(_) if little => Sample::Float(reader.read_le_to_f32()?),
+            Sample::Float(_) => Sample::Float(reader.read_be_to_f32()?),
+            Sample::DoubleFloat(_) if little => Sample::DoubleFloat(reader.read_le_to_f64()?),
+            Sample::DoubleFloat(_) => Sample::DoubleFloat(reader.read_be_to_f64()?),
+            Sample::ALaw(_) => Sample::Signed16bits(g711::decode_a_law(reader.read_to_u8()?)),
+            Sample::MuLaw(_) => Sample::Signed16bits(g711::decode_mu_law(reader.read_to_u8()?)),
+            s => return Err(PCMError::UnsupportedSampleType(s.clone())),
+        })
+    }
+    /// Returns true if both Samples are of the same type, regardless of their values
+    pub fn is_same_type(&self, other: &Sample) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+    }
+    /// Returns true for ADPCM types, whose samples are encoded in blocks and kept decoded in frames
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Sample::ImaADPCM(_) | Sample::MicrosoftADPCM(_))
+    }
+    /// Returns true for floating point types
+    pub fn is_float(&self) -> bool {
+        matches!(self, Sample::Float(_) | Sample::DoubleFloat(_))
+    }
     /// Returns how big a sample is in bits
     pub fn get_binary_size(&self) -> u16 {
         match self {
@@ -138,6 +747,103 @@ impl Sample {
             Sample::Float(_) => 32,
             Sample::DoubleFloat(_) => 64,
             Sample::ImaADPCM(_) => 4,
+            Sample::ALaw(_) => 8,
+            Sample::MuLaw(_) => 8,
+        }
+    }
+}
+
+/// Number of frames shown at the start and at the end when debug printing PCM data
+const DEBUG_FRAMES: usize = 3;
+
+impl fmt::Debug for PCM {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("PCM");
+        debug
+            .field("parameters", &self.parameters)
+            .field("loop_info", &self.loop_info)
+            .field("markers", &self.markers)
+            .field("broadcast_info", &self.broadcast_info)
+            .field("tags", &self.tags)
+            .field("nb_frames", &self.frames.len());
+        if self.frames.len() <= DEBUG_FRAMES * 2 {
+            debug.field("frames", &self.frames);
+        } else {
+            debug
+                .field("first_frames", &&self.frames[..DEBUG_FRAMES])
+                .field("last_frames", &&self.frames[self.frames.len() - DEBUG_FRAMES..]);
+        }
+        debug.finish()
+    }
+}
+
+impl IntoIterator for PCM {
+    type Item = Frame;
+    type IntoIter = <Vec<Frame> as IntoIterator>::IntoIter;
+    fn into_iter(self) -> Self::IntoIter {
+        self.frames.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a PCM {
+    type Item = &'a Frame;
+    type IntoIter = core::slice::Iter<'a, Frame>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.frames.iter()
+    }
+}
+
+impl PartialEq for Sample {
+    fn eq(&self, other: &Sample) -> bool {
+        match (self, other) {
+            (Sample::Unsigned8bits(a), Sample::Unsigned8bits(b)) => a == b,
+            (Sample::Signed16bits(a), Sample::Signed16bits(b)) => a == b,
+            (Sample::Signed24bits(a), Sample::Signed24bits(b)) => a == b,
+            (Sample::Signed32bits(a), Sample::Signed32bits(b)) => a == b,
+            (Sample::ImaADPCM(a), Sample::ImaADPCM(b)) => a == b,
+            (Sample::MicrosoftADPCM(a), Sample::MicrosoftADPCM(b)) => a == b,
+            (Sample::ALaw(a), Sample::ALaw(b)) => a == b,
+            (Sample::MuLaw(a), Sample::MuLaw(b)) => a == b,
+            (Sample::Float(a), Sample::Float(b)) => a.to_bits() == b.to_bits(),
+            (Sample::DoubleFloat(a), Sample::DoubleFloat(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Sample {}
+
+impl Hash for Sample {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Sample::Unsigned8bits(v) => v.hash(state),
+            Sample::Signed16bits(v) => v.hash(state),
+            Sample::Signed24bits(v) => v.hash(state),
+            Sample::Signed32bits(v) => v.hash(state),
+            Sample::ImaADPCM(v) => v.hash(state),
+            Sample::MicrosoftADPCM(v) => v.hash(state),
+            Sample::ALaw(v) => v.hash(state),
+            Sample::MuLaw(v) => v.hash(state),
+            Sample::Float(v) => v.to_bits().hash(state),
+            Sample::DoubleFloat(v) => v.to_bits().hash(state),
+        }
+    }
+}
+
+/// FNV-1a hasher, used to get hashes that stay the same between runs
+struct ContentHasher {
+    state: u64,
+}
+
+impl Hasher for ContentHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(0x0100_0000_01B3);
         }
     }
 }
@@ -153,6 +859,8 @@ impl fmt::Display for Sample {
             Sample::Float(_) => "Float 32 bits",
             Sample::DoubleFloat(_) => "Double-precision Float 64 bits",
             Sample::ImaADPCM(_) => "IMA ADPCM 4 bits",
+            Sample::ALaw(_) => "A-law 8 bits",
+            Sample::MuLaw(_) => "µ-law 8 bits",
         };
         write!(f, "{}", text)
     }
@@ -162,8 +870,11 @@ impl fmt::Display for Sample {
 mod tests {
     use std::fs::File;
     use std::io::{BufReader, BufWriter};
-    use std::time::Instant;
-    use PCM;
+    use std::io::Cursor;
+    use std::time::{Duration, Instant};
+    use error::PCMError;
+    use sample_types::{ALaw, ImaADPCM, I24, MicrosoftADPCM};
+    use {BTreeMap, Endianness, Frame, PCMParameters, Sample, PCM};
     #[test]
     fn wave_read_and_write() {
         let ref mut input_wave_reader = BufReader::new(File::open("test_files/input.wav").unwrap());
@@ -186,4 +897,282 @@ mod tests {
             output_pcm.elapsed().subsec_nanos()
         );
     }
+    #[test]
+    fn raw_export_endianness() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![Frame {
+                samples: vec![Sample::Signed16bits(0x0102), Sample::Signed16bits(-2)],
+            }],
+        };
+        let mut little = Cursor::new(Vec::new());
+        pcm.export_raw_file(&mut little).unwrap();
+        assert_eq!(little.into_inner(), vec![0x02, 0x01, 0xFE, 0xFF]);
+        let mut big = Cursor::new(Vec::new());
+        pcm.export_raw_file_endian(&mut big, Endianness::Big).unwrap();
+        assert_eq!(big.into_inner(), vec![0x01, 0x02, 0xFF, 0xFE]);
+    }
+    #[test]
+    fn write_interleaved() {
+        let mut pcm = PCM {
+            parameters: PCMParameters::new(8000, 1, Sample::Signed24bits(I24 { value: 0 })).unwrap(),
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![
+                Frame {
+                    samples: vec![Sample::Signed24bits(I24 { value: -0x010203 })],
+                };
+                5000
+            ],
+        };
+        let mut little = Vec::new();
+        pcm.write_interleaved(&mut little, Endianness::Little).unwrap();
+        assert_eq!(little.len(), 15000);
+        assert_eq!(&little[14997..], &[0xFD, 0xFD, 0xFE]);
+        let mut big = Vec::new();
+        pcm.write_interleaved(&mut big, Endianness::Big).unwrap();
+        assert_eq!(&big[..3], &[0xFE, 0xFD, 0xFD]);
+        pcm.frames[4500].samples[0] = Sample::Signed16bits(0);
+        match pcm.write_interleaved(&mut Vec::new(), Endianness::Little) {
+            Err(PCMError::WrongSampleType(4500, Sample::Signed16bits(_))) => {}
+            _ => panic!(),
+        }
+        let parameters = PCMParameters::new(8000, 2, Sample::ImaADPCM(ImaADPCM {})).unwrap();
+        let mut ima = PCM::empty(parameters);
+        for _ in 0..3 {
+            ima.frames.push(Frame {
+                samples: vec![Sample::Signed16bits(0); 2],
+            });
+        }
+        ima.frames[2].samples.pop();
+        match ima.write_interleaved(&mut Vec::new(), Endianness::Little) {
+            Err(PCMError::WrongNumberOfSamples(2, 1)) => {}
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn raw_import() {
+        let parameters = PCMParameters {
+            sample_rate: 8000,
+            nb_channels: 2,
+            sample_type: Sample::Signed24bits(I24 { value: 0 }),
+            channel_mask: None,
+        };
+        let mut raw = Cursor::new(vec![0x01, 0x02, 0x03, 0xFF, 0xFF, 0xFF]);
+        let pcm = PCM::import_raw_file(&mut raw, parameters.clone()).unwrap();
+        assert_eq!(pcm.frames.len(), 1);
+        match (&pcm.frames[0].samples[0], &pcm.frames[0].samples[1]) {
+            (Sample::Signed24bits(a), Sample::Signed24bits(b)) => {
+                assert_eq!(a.value, 0x030201);
+                assert_eq!(b.value, -1);
+            }
+            _ => panic!(),
+        }
+        let mut partial = Cursor::new(vec![0u8; 8]);
+        match PCM::import_raw_file(&mut partial, parameters) {
+            Err(PCMError::PartialFrame(2)) => {}
+            _ => panic!(),
+        }
+        let adpcm = PCMParameters::new(8000, 1, Sample::MicrosoftADPCM(MicrosoftADPCM {})).unwrap();
+        match PCM::import_raw_file(&mut Cursor::new(vec![0u8; 16]), adpcm) {
+            Err(PCMError::UnsupportedSampleType(Sample::MicrosoftADPCM(_))) => {}
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn validate_frames() {
+        let mut pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![
+                Frame {
+                    samples: vec![Sample::Signed16bits(0), Sample::Signed16bits(1)],
+                };
+                3
+            ],
+        };
+        assert!(pcm.validate().is_ok());
+        pcm.frames[1].samples.pop();
+        match pcm.validate() {
+            Err(PCMError::WrongNumberOfSamples(1, 1)) => {}
+            _ => panic!(),
+        }
+        pcm.frames[1].samples.push(Sample::Float(0f32));
+        match pcm.validate() {
+            Err(PCMError::WrongSampleType(1, Sample::Float(_))) => {}
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn frame_get_set() {
+        let mut frame = Frame {
+            samples: vec![Sample::Signed16bits(1), Sample::Signed16bits(2)],
+        };
+        assert_eq!(frame.get(1), Some(&Sample::Signed16bits(2)));
+        assert_eq!(frame.get(2), None);
+        frame.set(0, Sample::Signed16bits(-1)).unwrap();
+        assert_eq!(frame.get(0), Some(&Sample::Signed16bits(-1)));
+        match frame.set(2, Sample::Signed16bits(0)) {
+            Err(PCMError::ChannelOutOfRange(2)) => {}
+            _ => panic!(),
+        }
+        match frame.set(1, Sample::Float(0f32)) {
+            Err(PCMError::UnsupportedSampleType(Sample::Float(_))) => {}
+            _ => panic!(),
+        }
+        assert_eq!(frame.get(1), Some(&Sample::Signed16bits(2)));
+    }
+    #[test]
+    fn into_iter() {
+        let mut pcm = PCM::empty(PCMParameters::new(8000, 1, Sample::Signed16bits(0)).unwrap());
+        pcm.frames = (0..3)
+            .map(|i| Frame {
+                samples: vec![Sample::Signed16bits(i)],
+            })
+            .collect();
+        let mut i = 0;
+        for frame in &pcm {
+            assert_eq!(frame.get(0), Some(&Sample::Signed16bits(i)));
+            i += 1;
+        }
+        assert_eq!(i, 3);
+        let frames: Vec<Frame> = pcm.clone().into_iter().collect();
+        assert_eq!(frames, pcm.frames);
+    }
+    #[test]
+    fn sample_categories() {
+        assert!(Sample::MicrosoftADPCM(MicrosoftADPCM {}).is_compressed());
+        assert!(!Sample::ALaw(ALaw {}).is_compressed());
+        assert!(Sample::DoubleFloat(0f64).is_float());
+        assert!(!Sample::Signed32bits(0).is_float());
+    }
+    #[test]
+    fn empty() {
+        let pcm = PCM::empty(PCMParameters::default());
+        assert!(pcm.validate().is_ok());
+        assert_eq!(pcm.frame_count(), 0);
+        assert_eq!(pcm.parameters.byte_rate(), 176_400);
+    }
+    #[test]
+    fn checked_parameters() {
+        let parameters = PCMParameters::new(44100, 2, Sample::Signed16bits(0)).unwrap();
+        assert_eq!(parameters.byte_rate(), 176_400);
+        match PCMParameters::new(0, 2, Sample::Signed16bits(0)) {
+            Err(PCMError::InvalidParameters(_)) => {}
+            _ => panic!(),
+        }
+        match PCMParameters::new(44100, 0, Sample::Signed16bits(0)) {
+            Err(PCMError::InvalidParameters(_)) => {}
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn duration_and_counts() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 4,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![
+                Frame {
+                    samples: vec![Sample::Signed16bits(0), Sample::Signed16bits(0)],
+                };
+                7
+            ],
+        };
+        assert_eq!(pcm.get_audio_duration(), Duration::from_millis(1750));
+        let mut no_rate = pcm.clone();
+        no_rate.parameters.sample_rate = 0;
+        assert_eq!(no_rate.get_audio_duration(), Duration::ZERO);
+        assert_eq!(pcm.frame_count(), 7);
+        assert_eq!(pcm.sample_count(), 14);
+        assert_eq!(pcm.parameters.bytes_per_sample(), 2);
+        assert_eq!(pcm.parameters.bytes_per_frame(), 4);
+        assert_eq!(pcm.parameters.byte_rate(), 16);
+        assert_eq!(pcm.frame_index_at_time(Duration::from_millis(499)), 1);
+        assert_eq!(pcm.frame_index_at_time(Duration::from_millis(500)), 2);
+        assert!(pcm.frame_at_time(Duration::from_millis(1749)).is_some());
+        assert!(pcm.frame_at_time(Duration::from_millis(1750)).is_none());
+    }
+    #[test]
+    fn equality_and_hash() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::DoubleFloat(0f64),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![
+                Frame {
+                    samples: vec![Sample::DoubleFloat(::std::f64::NAN)],
+                },
+                Frame {
+                    samples: vec![Sample::DoubleFloat(0.5)],
+                },
+            ],
+        };
+        let mut other = pcm.clone();
+        assert!(pcm == other);
+        assert_eq!(pcm.content_hash(), other.content_hash());
+        other.frames[1].samples[0] = Sample::DoubleFloat(-0.5);
+        assert!(pcm != other);
+        assert_ne!(pcm.content_hash(), other.content_hash());
+        assert!(Sample::DoubleFloat(0f64) != Sample::DoubleFloat(-0f64));
+        assert!(Sample::Signed16bits(0) != Sample::Signed32bits(0));
+    }
+    #[test]
+    fn debug_summary() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Unsigned8bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..100u8)
+                .map(|i| Frame {
+                    samples: vec![Sample::Unsigned8bits(i)],
+                })
+                .collect(),
+        };
+        let text = format!("{:?}", pcm);
+        assert!(text.contains("nb_frames: 100"));
+        assert!(text.contains("Unsigned8bits(2)"));
+        assert!(text.contains("Unsigned8bits(99)"));
+        assert!(!text.contains("Unsigned8bits(50)"));
+    }
 }
@@ -0,0 +1,195 @@
+use {Frame, PCMError, Result, Sample};
+
+/// Scales the quantization step depending on the last nibble
+const ADAPTATION_TABLE: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+
+/// Highest step size, so that it can still be multiplied by the adaptation table
+const MAX_DELTA: i32 = i32::MAX / 768;
+
+/// Decoder state for a single channel
+struct ChannelState {
+    coefficient_1: i32,
+    coefficient_2: i32,
+    delta: i32,
+    sample_1: i32,
+    sample_2: i32,
+}
+
+impl ChannelState {
+    /// Expands a nibble into a sample
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let signed_nibble = if nibble >= 8 {
+            i32::from(nibble) - 16
+        } else {
+            i32::from(nibble)
+        };
+        // Samples and coefficients of -32768 would overflow an i32
+        let predictor = (i64::from(self.sample_1) * i64::from(self.coefficient_1)
+            + i64::from(self.sample_2) * i64::from(self.coefficient_2))
+            / 256;
+        let sample = (predictor + i64::from(signed_nibble * self.delta))
+            .clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i32;
+        self.sample_2 = self.sample_1;
+        self.sample_1 = sample;
+        self.delta = (ADAPTATION_TABLE[nibble as usize] * self.delta / 256).clamp(16, MAX_DELTA);
+        sample as i16
+    }
+}
+
+/// Decodes Microsoft ADPCM blocks into frames of Signed 16 bits samples, using the coefficient pairs found in the format chunk
+pub fn decode(
+    data: &[u8],
+    nb_channels: u16,
+    block_align: u16,
+    coefficients: &[(i16, i16)],
+) -> Result<Vec<Frame>> {
+    if nb_channels == 0 {
+        return Err(PCMError::InvalidParameters("0 channels"));
+    }
+    if block_align == 0 {
+        return Err(PCMError::InvalidBlockAlign(block_align));
+    }
+    let nb_channels = nb_channels as usize;
+    let mut frames = Vec::new();
+    for block in data.chunks(block_align as usize) {
+        if block.len() < 7 * nb_channels {
+            break;
+        }
+        // Each field of the block header is interleaved between channels
+        let read_i16 =
+            |position: usize| i16::from(block[position]) | (i16::from(block[position + 1]) << 8);
+        let mut states = Vec::with_capacity(nb_channels);
+        for channel in 0..nb_channels {
+            let predictor = block[channel];
+            let (coefficient_1, coefficient_2) = match coefficients.get(predictor as usize) {
+                Some(c) => *c,
+                None => return Err(PCMError::UnknownADPCMPredictor(predictor)),
+            };
+            states.push(ChannelState {
+                coefficient_1: i32::from(coefficient_1),
+                coefficient_2: i32::from(coefficient_2),
+                delta: i32::from(read_i16(nb_channels + channel * 2)),
+                sample_1: i32::from(read_i16(nb_channels * 3 + channel * 2)),
+                sample_2: i32::from(read_i16(nb_channels * 5 + channel * 2)),
+            });
+        }
+        frames.push(Frame {
+            samples: states
+                .iter()
+                .map(|s| Sample::Signed16bits(s.sample_2 as i16))
+                .collect(),
+        });
+        frames.push(Frame {
+            samples: states
+                .iter()
+                .map(|s| Sample::Signed16bits(s.sample_1 as i16))
+                .collect(),
+        });
+        // Nibbles alternate between channels, higher nibble first
+        let mut samples = Vec::with_capacity(nb_channels);
+        for byte in &block[7 * nb_channels..] {
+            for nibble in &[byte >> 4, byte & 0x0F] {
+                let channel = samples.len();
+                samples.push(Sample::Signed16bits(states[channel].decode(*nibble)));
+                if samples.len() == nb_channels {
+                    frames.push(Frame { samples });
+                    samples = Vec::with_capacity(nb_channels);
+                }
+            }
+        }
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+    use error::PCMError;
+    use ez_io::WriteE;
+    use std::io::{Cursor, Write};
+    use {Sample, PCM};
+    #[test]
+    fn import_wave() {
+        let coefficients: [(i16, i16); 7] = [
+            (256, 0),
+            (512, -256),
+            (0, 0),
+            (192, 64),
+            (240, 0),
+            (460, -208),
+            (392, -232),
+        ];
+        let mut wave = Cursor::new(Vec::new());
+        wave.write_all(b"RIFF").unwrap();
+        wave.write_le_to_u32(4 + 58 + 17).unwrap();
+        wave.write_all(b"WAVEfmt ").unwrap();
+        wave.write_le_to_u32(50).unwrap();
+        wave.write_le_to_u16(2).unwrap(); // Microsoft ADPCM
+        wave.write_le_to_u16(1).unwrap();
+        wave.write_le_to_u32(8000).unwrap();
+        wave.write_le_to_u32(12000).unwrap();
+        wave.write_le_to_u16(9).unwrap();
+        wave.write_le_to_u16(4).unwrap();
+        wave.write_le_to_u16(32).unwrap();
+        wave.write_le_to_u16(6).unwrap();
+        wave.write_le_to_u16(7).unwrap();
+        for (coefficient_1, coefficient_2) in &coefficients {
+            wave.write_le_to_i16(*coefficient_1).unwrap();
+            wave.write_le_to_i16(*coefficient_2).unwrap();
+        }
+        wave.write_all(b"data").unwrap();
+        wave.write_le_to_u32(9).unwrap();
+        wave.write_to_u8(1).unwrap(); // Predictor
+        wave.write_le_to_i16(20).unwrap(); // Delta
+        wave.write_le_to_i16(100).unwrap(); // Sample 1
+        wave.write_le_to_i16(50).unwrap(); // Sample 2
+        wave.write_all(&[0x12, 0xF0]).unwrap();
+        wave.set_position(0);
+        let pcm = PCM::wave_import_file(&mut wave).unwrap();
+        // Reference values computed by hand from the specification
+        let expected = [50i16, 100, 170, 274, 362, 450];
+        assert_eq!(pcm.frames.len(), expected.len());
+        for (frame, expected) in pcm.frames.iter().zip(expected.iter()) {
+            match frame.samples[0] {
+                Sample::Signed16bits(v) => assert_eq!(v, *expected),
+                _ => panic!(),
+            }
+        }
+    }
+    #[test]
+    fn decode_stereo() {
+        let coefficients = [(256, 0), (512, -256)];
+        // Predictors, deltas, first and second samples, each field holding left then right
+        let block = [
+            0, 1, 16, 0, 32, 0, 100, 0, 0x38, 0xFF, 50, 0, 0x9C, 0xFF, 0x1F, 0x72,
+        ];
+        let frames = decode(&block, 2, block.len() as u16, &coefficients).unwrap();
+        // Reference values computed by hand from the specification
+        let expected = [(50, -100), (100, -200), (116, -332), (228, -408)];
+        assert_eq!(frames.len(), expected.len());
+        for (frame, &(left, right)) in frames.iter().zip(expected.iter()) {
+            assert_eq!(
+                frame.samples,
+                vec![Sample::Signed16bits(left), Sample::Signed16bits(right)]
+            );
+        }
+    }
+    #[test]
+    fn decode_extreme_values() {
+        // Largest step increases with samples and coefficients at their minimum
+        let mut block = vec![0, 0xFF, 0x7F, 0x00, 0x80, 0x00, 0x80];
+        block.extend_from_slice(&[0x88; 32]);
+        let frames = decode(&block, 1, block.len() as u16, &[(-32768, -32768)]).unwrap();
+        assert_eq!(frames.len(), 2 + 64);
+        match decode(&block, 1, 0, &[(256, 0)]) {
+            Err(PCMError::InvalidBlockAlign(0)) => {}
+            _ => panic!(),
+        }
+        match decode(&block, 0, 7, &[(256, 0)]) {
+            Err(PCMError::InvalidParameters(_)) => {}
+            _ => panic!(),
+        }
+    }
+}
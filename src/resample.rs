@@ -0,0 +1,198 @@
+use std::f64::consts::PI;
+use {BroadcastInfo, CuePoint, Frame, LoopInfo, Sample, PCM};
+
+/// Number of zero crossings of the sinc function used on each side of a sample by resample_sinc
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResampleQuality {
+    /// 8 zero crossings
+    Low,
+    /// 32 zero crossings
+    Medium,
+    /// 128 zero crossings
+    High,
+    /// A custom number of zero crossings, at least 1
+    Custom(usize),
+}
+
+impl ResampleQuality {
+    /// Returns the number of zero crossings on each side
+    pub fn zero_crossings(self) -> usize {
+        match self {
+            ResampleQuality::Low => 8,
+            ResampleQuality::Medium => 32,
+            ResampleQuality::High => 128,
+            ResampleQuality::Custom(n) => n.max(1),
+        }
+    }
+}
+
+impl PCM {
+    /// Returns a copy of this PCM at another sample rate, using linear interpolation between frames.
+    /// The new frame count is round(old frame count * target_rate / old sample rate).
+    pub fn resample(&self, target_rate: u32) -> PCM {
+        let ratio = f64::from(target_rate) / f64::from(self.parameters.sample_rate);
+        let nb_frames = (self.frames.len() as f64 * ratio).round() as usize;
+        let mut frames = Vec::with_capacity(nb_frames);
+        if !self.frames.is_empty() {
+            let last = self.frames.len() - 1;
+            for frame_id in 0..nb_frames {
+                let position = frame_id as f64 / ratio;
+                let before = (position.floor() as usize).min(last);
+                let after = (before + 1).min(last);
+                let fraction = position - before as f64;
+                frames.push(Frame {
+                    samples: self.frames[before]
+                        .samples
+                        .iter()
+                        .zip(self.frames[after].samples.iter())
+                        .map(|(b, a)| {
+                            let value = b.to_f64() * (1f64 - fraction) + a.to_f64() * fraction;
+                            Sample::from_f64(value, b)
+                        })
+                        .collect(),
+                });
+            }
+        }
+        self.with_rate(target_rate, frames)
+    }
+    /// Returns a copy of this PCM at another sample rate, using windowed-sinc interpolation.
+    /// Frequencies above the lower of both Nyquist frequencies are filtered out, the frame count is the same as resample.
+    pub fn resample_sinc(&self, target_rate: u32, quality: ResampleQuality) -> PCM {
+        let ratio = f64::from(target_rate) / f64::from(self.parameters.sample_rate);
+        // Lowering the cutoff of the sinc when downsampling prevents aliasing
+        let cutoff = ratio.min(1f64);
+        let half_width = quality.zero_crossings() as f64 / cutoff;
+        let nb_frames = (self.frames.len() as f64 * ratio).round() as usize;
+        let nb_channels = self.parameters.nb_channels as usize;
+        let mut frames = Vec::with_capacity(nb_frames);
+        let mut values = vec![0f64; nb_channels];
+        for frame_id in 0..nb_frames {
+            let position = frame_id as f64 / ratio;
+            let first = (position - half_width).ceil().max(0f64) as usize;
+            let last = ((position + half_width).floor() as usize).min(self.frames.len() - 1);
+            values.fill(0f64);
+            for (source_id, source) in self.frames.iter().enumerate().take(last + 1).skip(first) {
+                let distance = position - source_id as f64;
+                let weight = cutoff * sinc(cutoff * distance) * blackman(distance / half_width);
+                for (value, sample) in values.iter_mut().zip(source.samples.iter()) {
+                    *value += sample.to_f64() * weight;
+                }
+            }
+            frames.push(Frame {
+                samples: values
+                    .iter()
+                    .map(|v| Sample::from_f64(*v, &self.parameters.sample_type))
+                    .collect(),
+            });
+        }
+        self.with_rate(target_rate, frames)
+    }
+    /// Returns a PCM made of frames at another sample rate, with the positions of the metadata scaled accordingly
+    fn with_rate(&self, target_rate: u32, frames: Vec<Frame>) -> PCM {
+        let ratio = f64::from(target_rate) / f64::from(self.parameters.sample_rate);
+        let mut parameters = self.parameters.clone();
+        parameters.sample_rate = target_rate;
+        PCM {
+            parameters,
+            loop_info: self.loop_info.as_ref().map(|loops| {
+                loops
+                    .iter()
+                    .map(|l| LoopInfo {
+                        loop_start: (l.loop_start as f64 * ratio).round() as u64,
+                        loop_end: (l.loop_end as f64 * ratio).round() as u64,
+                    })
+                    .collect()
+            }),
+            markers: self
+                .markers
+                .iter()
+                .map(|m| CuePoint {
+                    position: (m.position as f64 * ratio).round() as u64,
+                    ..m.clone()
+                })
+                .collect(),
+            broadcast_info: self.broadcast_info.as_ref().map(|b| BroadcastInfo {
+                time_reference: (b.time_reference as f64 * ratio).round() as u64,
+                ..b.clone()
+            }),
+            tags: self.tags.clone(),
+            frames,
+        }
+    }
+}
+
+/// Normalized sinc function
+fn sinc(x: f64) -> f64 {
+    if x == 0f64 {
+        1f64
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Blackman window over -1 to 1
+fn blackman(x: f64) -> f64 {
+    0.42 + 0.5 * (PI * x).cos() + 0.08 * (2f64 * PI * x).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResampleQuality;
+    use std::f64::consts::PI;
+    use std::time::Duration;
+    use {BTreeMap, Frame, PCMParameters, Sample, PCM};
+    #[test]
+    fn resample_length() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 48000,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..480)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i * 10)],
+                })
+                .collect(),
+        };
+        let resampled = pcm.resample(44100);
+        assert_eq!(resampled.parameters.sample_rate, 44100);
+        assert_eq!(resampled.frames.len(), 441);
+        // A ramp stays a ramp
+        match (&resampled.frames[0].samples[0], &resampled.frames[100].samples[0]) {
+            (Sample::Signed16bits(first), Sample::Signed16bits(v)) => {
+                assert_eq!(*first, 0);
+                assert!((i32::from(*v) - 1088).abs() <= 1);
+            }
+            _ => panic!(),
+        }
+    }
+    /// Returns the peak of a PCM, ignoring the edges
+    fn middle_peak(pcm: &PCM) -> f64 {
+        let len = pcm.frames.len();
+        pcm.frames[len / 4..len * 3 / 4]
+            .iter()
+            .map(|f| f.samples[0].to_f64().abs())
+            .fold(0f64, f64::max)
+    }
+    #[test]
+    fn resample_sinc() {
+        let duration = Duration::from_millis(50);
+        let tone = PCM::generate_sine(48000, 1000f64, duration, 0.5, Sample::Float(0f32));
+        let resampled = tone.resample_sinc(44100, ResampleQuality::Medium);
+        assert_eq!(resampled.frames.len(), tone.resample(44100).frames.len());
+        for (i, frame) in resampled.frames.iter().enumerate().skip(200).take(1000) {
+            let expected = (2f64 * PI * 1000f64 * i as f64 / 44100f64).sin() * 0.5;
+            assert!((frame.samples[0].to_f64() - expected).abs() < 0.001);
+        }
+        // A tone above the new Nyquist frequency is removed instead of folding back
+        let high = PCM::generate_sine(48000, 15000f64, duration, 0.5, Sample::Float(0f32));
+        assert!(middle_peak(&high.resample_sinc(16000, ResampleQuality::Medium)) < 0.01);
+        assert!(middle_peak(&high.resample(16000)) > 0.1);
+    }
+}
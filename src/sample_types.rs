@@ -1,8 +1,20 @@
-#[derive(Clone, Debug)]
-pub struct I24 {}
+/// A signed 24 bits value, stored in a 32 bits integer
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct I24 {
+    /// Value of the sample, between -8388608 and 8388607
+    pub value: i32,
+}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ImaADPCM {}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct MicrosoftADPCM {}
+
+/// A-law companded byte, decoded as Signed 16 bits
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ALaw {}
+
+/// µ-law companded byte, decoded as Signed 16 bits
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MuLaw {}
@@ -0,0 +1,81 @@
+use sample_types::{ALaw, I24, ImaADPCM, MicrosoftADPCM, MuLaw};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use Sample;
+
+/// Type of a Sample without its value, which is all parameters need to keep
+#[derive(Serialize, Deserialize)]
+enum SampleTag {
+    Unsigned8bits,
+    Signed16bits,
+    Signed24bits,
+    Signed32bits,
+    ImaADPCM,
+    MicrosoftADPCM,
+    ALaw,
+    MuLaw,
+    Float,
+    DoubleFloat,
+}
+
+/// Serializes a Sample as the name of its type
+pub fn serialize<S: Serializer>(sample: &Sample, serializer: S) -> Result<S::Ok, S::Error> {
+    match sample {
+        Sample::Unsigned8bits(_) => SampleTag::Unsigned8bits,
+        Sample::Signed16bits(_) => SampleTag::Signed16bits,
+        Sample::Signed24bits(_) => SampleTag::Signed24bits,
+        Sample::Signed32bits(_) => SampleTag::Signed32bits,
+        Sample::ImaADPCM(_) => SampleTag::ImaADPCM,
+        Sample::MicrosoftADPCM(_) => SampleTag::MicrosoftADPCM,
+        Sample::ALaw(_) => SampleTag::ALaw,
+        Sample::MuLaw(_) => SampleTag::MuLaw,
+        Sample::Float(_) => SampleTag::Float,
+        Sample::DoubleFloat(_) => SampleTag::DoubleFloat,
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes the name of a type into a Sample of that type holding silence
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Sample, D::Error> {
+    Ok(match SampleTag::deserialize(deserializer)? {
+        SampleTag::Unsigned8bits => Sample::Unsigned8bits(128),
+        SampleTag::Signed16bits => Sample::Signed16bits(0),
+        SampleTag::Signed24bits => Sample::Signed24bits(I24 { value: 0 }),
+        SampleTag::Signed32bits => Sample::Signed32bits(0),
+        SampleTag::ImaADPCM => Sample::ImaADPCM(ImaADPCM {}),
+        SampleTag::MicrosoftADPCM => Sample::MicrosoftADPCM(MicrosoftADPCM {}),
+        SampleTag::ALaw => Sample::ALaw(ALaw {}),
+        SampleTag::MuLaw => Sample::MuLaw(MuLaw {}),
+        SampleTag::Float => Sample::Float(0f32),
+        SampleTag::DoubleFloat => Sample::DoubleFloat(0f64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use {LoopInfo, PCMParameters, Sample};
+    #[test]
+    fn parameters_round_trip() {
+        let parameters = PCMParameters {
+            sample_rate: 48000,
+            nb_channels: 2,
+            sample_type: Sample::Signed24bits(::sample_types::I24 { value: 1234 }),
+            channel_mask: Some(0x3),
+        };
+        let json = serde_json::to_string(&parameters).unwrap();
+        assert_eq!(
+            json,
+            r#"{"sample_rate":48000,"nb_channels":2,"sample_type":"Signed24bits","channel_mask":3}"#
+        );
+        let read: PCMParameters = serde_json::from_str(&json).unwrap();
+        assert!(read.sample_type.is_same_type(&parameters.sample_type));
+        assert_eq!(read.sample_rate, 48000);
+        assert_eq!(read.channel_mask, Some(0x3));
+        let loop_info = LoopInfo {
+            loop_start: 10,
+            loop_end: 200,
+        };
+        let json = serde_json::to_string(&loop_info).unwrap();
+        assert_eq!(serde_json::from_str::<LoopInfo>(&json).unwrap(), loop_info);
+    }
+}
@@ -1,79 +1,897 @@
 use ez_io::{ReadE, WriteE};
 use magic_number::check_magic_number;
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
-use {Frame, PCMError, PCMParameters, Result, Sample, PCM};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use sample_types::{ALaw, I24, ImaADPCM, MicrosoftADPCM, MuLaw};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::time::Duration;
+use {
+    ima_adpcm, microsoft_adpcm, read_bytes, BTreeMap, BroadcastInfo, CuePoint, Endianness, Frame,
+    LoopInfo, PCMError, PCMParameters, Result, Sample, MAX_PREALLOCATION, PCM,
+};
 
-impl PCM {
-    /// Imports a Wave file and returns a corresponding PCM
-    pub fn wave_import_file<R: Read + Seek>(reader: &mut R) -> Result<PCM> {
-        check_magic_number(reader, vec![b'R', b'I', b'F', b'F'])?;
-        let _chunk_size = reader.read_le_to_u32()?;
+/// Size of the data chunk written by software that does not know it beforehand, the audio data going on until the end of the file
+const UNKNOWN_DATA_SIZE: u32 = 0xFFFF_FFFF;
+
+/// Last 14 bytes of the Sub Format GUIDs in a WAVE_FORMAT_EXTENSIBLE header, the first two being the actual format
+const EXTENSIBLE_SUB_FORMAT_SUFFIX: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// Number of bytes of audio data read at once when importing a whole file
+const IMPORT_BATCH_SIZE: u64 = 1 << 16;
+
+/// Size of a ds64 chunk without any table entry
+const DS64_SIZE: u64 = 28;
+
+/// Size of the bext chunk without the coding history
+const BEXT_FIXED_SIZE: u32 = 602;
+
+/// Contents of the format chunk, needed to decode the audio data
+struct WaveFormat {
+    parameters: PCMParameters,
+    endian: Endianness,
+    block_align: u16,
+    coefficients: Vec<(i16, i16)>,
+}
+
+/// Information found in chunks that are not needed to decode the audio data
+#[derive(Default)]
+struct WaveMetadata {
+    loop_info: Option<Vec<LoopInfo>>,
+    cue_points: Vec<(u32, u64)>,
+    labels: Vec<(u32, String)>,
+    sample_length: Option<u32>,
+    broadcast_info: Option<BroadcastInfo>,
+    tags: BTreeMap<[u8; 4], String>,
+    peaks: Option<Vec<ChannelPeak>>,
+}
+
+/// Description of the audio in a Wave file, obtained without decoding it
+#[derive(Clone, Debug)]
+pub struct WaveInfo {
+    /// Parameters of the signal
+    pub parameters: PCMParameters,
+    /// Number of frames in the audio data
+    pub nb_frames: u64,
+    /// Position of the audio data in the reader, after the header of the data chunk
+    pub data_offset: u64,
+    /// Size of the audio data in bytes, measured up to the end of the file when the data chunk does not tell it
+    pub data_size: u64,
+    /// Size in bytes of a frame, or of an ADPCM block
+    pub block_align: u16,
+    /// Peak of each channel if the file has a PEAK chunk before the audio data, as float files often do.
+    /// Only probing gives them, imported PCMs do not keep them and they are computed again when exporting float samples.
+    pub peaks: Option<Vec<ChannelPeak>>,
+}
+
+/// Peak of a channel, as found in the PEAK chunk of a Wave file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelPeak {
+    /// Biggest absolute value, normalized between 0 and 1
+    pub value: f32,
+    /// Frame where this value is first reached
+    pub position: u64,
+}
+
+impl WaveInfo {
+    /// Get the duration of the signal
+    pub fn get_audio_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.nb_frames as f64 / f64::from(self.parameters.sample_rate))
+    }
+    /// Returns the position in the reader of each ADPCM block, from which decoding can start over, empty for other types.
+    /// The last block may be shorter than block_align.
+    pub fn adpcm_block_offsets(&self) -> Vec<u64> {
+        if !self.parameters.sample_type.is_compressed() || self.block_align == 0 {
+            return Vec::new();
+        }
+        (0..self.data_size)
+            .step_by(self.block_align as usize)
+            .map(|offset| self.data_offset + offset)
+            .collect()
+    }
+}
+
+/// A whole Wave file computed in memory, ready to be written or sent elsewhere
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WaveImage {
+    /// Chunks before the audio data, up to the header of the data chunk
+    pub header: Vec<u8>,
+    /// Interleaved little-endian samples, or IMA ADPCM blocks
+    pub data: Vec<u8>,
+    /// Chunks after the audio data, starting with a padding byte if the size of the audio data is odd
+    pub trailer: Vec<u8>,
+}
+
+impl WaveImage {
+    /// Returns the position of the audio data in the file
+    pub fn data_offset(&self) -> usize {
+        self.header.len()
+    }
+    /// Returns the size of the whole file in bytes
+    pub fn size(&self) -> usize {
+        self.header.len() + self.data.len() + self.trailer.len()
+    }
+    /// Writes the whole file
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.header)?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.trailer)?;
+        Ok(())
+    }
+    /// Returns the whole file as a single buffer
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = self.header;
+        bytes.reserve(self.data.len() + self.trailer.len());
+        bytes.extend_from_slice(&self.data);
+        bytes.extend_from_slice(&self.trailer);
+        bytes
+    }
+}
+
+impl TryFrom<&PCM> for WaveImage {
+    type Error = PCMError;
+    /// Computes the Wave file that wave_export_file would write
+    fn try_from(pcm: &PCM) -> Result<WaveImage> {
+        let block_align = pcm
+            .parameters
+            .sample_type
+            .wave_get_block_align(pcm.parameters.nb_channels);
+        let (header, trailer) = pcm.wave_chunks(block_align, false)?;
+        let mut data = Vec::with_capacity(pcm.get_audio_size());
+        pcm.wave_write_data(&mut data, block_align)?;
+        Ok(WaveImage {
+            header,
+            data,
+            trailer,
+        })
+    }
+}
+
+/// Information found in the header of a Wave file
+struct WaveHeader {
+    format: WaveFormat,
+    metadata: WaveMetadata,
+    data_size: u64,
+    data_chunk_offset: u64,
+    riff_end: u64,
+}
+
+impl WaveHeader {
+    /// Reads the header of a Wave file, leaving the reader at the start of the audio data
+    fn import<R: Read + Seek>(reader: &mut R) -> Result<WaveHeader> {
+        let riff_start = reader.seek(SeekFrom::Current(0))?;
+        let mut riff_id = [0u8; 4];
+        reader.read_exact(&mut riff_id)?;
+        // RF64 and BW64 files have the same layout, with sizes over 32 bits in a ds64 chunk
+        let is_rf64 = &riff_id == b"RF64" || &riff_id == b"BW64";
+        // RIFX files store all numbers in big-endian
+        let endian = if &riff_id == b"RIFX" {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        };
+        if !is_rf64 && endian == Endianness::Little {
+            reader.seek(SeekFrom::Start(riff_start))?;
+            check_magic_number(reader, vec![b'R', b'I', b'F', b'F'])?;
+        }
+        let chunk_size = reader.read_u32_endian(endian)?;
         check_magic_number(reader, vec![b'W', b'A', b'V', b'E'])?;
-        check_magic_number(reader, vec![b'f', b'm', b't', b' '])?;
-        let _sub_chunk_1_size = reader.read_le_to_u32()?;
-        let audio_format = reader.read_le_to_u16()?;
-        if audio_format != 1 {
-            unimplemented!("Cannot work with wave files not using format 1 for now");
-        }
-        let nb_channels = reader.read_le_to_u16()?;
-        let sample_rate = reader.read_le_to_u32()?;
-        let _byte_rate = reader.read_le_to_u32()?;
-        let _block_align = reader.read_le_to_u16()?;
-        let bits_per_sample = reader.read_le_to_u16()?;
-        let sample_type = Sample::wave_from_format_bps(&audio_format, &bits_per_sample)?;
-        let parameters = PCMParameters {
-            sample_rate,
-            nb_channels,
-            sample_type: sample_type.clone(),
-        };
-        check_magic_number(reader, vec![b'd', b'a', b't', b'a'])?;
-        let sub_chunk_2_size = reader.read_le_to_u32()?;
-        let mut data = vec![0u8; sub_chunk_2_size as usize];
-        reader.read_exact(&mut data)?;
-        let mut pcm_raw = Cursor::new(data);
-        let mut frames = Vec::with_capacity(
-            (sub_chunk_2_size as usize / (bits_per_sample as usize / 8)) / nb_channels as usize,
-        );
-        let data_end = u64::from(sub_chunk_2_size);
-        while pcm_raw.seek(SeekFrom::Current(0))? < data_end {
-            let mut samples = Vec::with_capacity(nb_channels as usize);
-            for _ in 0..nb_channels {
-                match sample_type {
-                    Sample::Unsigned8bits(_) => {
-                        samples.push(Sample::Unsigned8bits(pcm_raw.read_to_u8()?))
+        let mut riff_end = riff_start + 8 + u64::from(chunk_size);
+        let mut ds64_data_size = None;
+        if is_rf64 {
+            let ds64_offset = reader.seek(SeekFrom::Current(0))?;
+            check_magic_number(reader, vec![b'd', b's', b'6', b'4'])
+                .map_err(|e| PCMError::from(e).in_chunk(*b"ds64", ds64_offset))?;
+            let ds64_size = reader.read_le_to_u32()?;
+            let riff_size = reader.read_le_to_u64()?;
+            ds64_data_size = Some(reader.read_le_to_u64()?);
+            if chunk_size == <u32>::max_value() {
+                riff_end = riff_start + 8 + riff_size;
+            }
+            // Number of frames and size of other chunks are not needed
+            reader.seek(SeekFrom::Start(
+                ds64_offset + 8 + u64::from(ds64_size) + u64::from(ds64_size & 1),
+            ))?;
+        }
+        let mut format = None;
+        let mut metadata = WaveMetadata::default();
+        loop {
+            let (chunk_id, chunk_size) = match next_chunk(reader, riff_end, endian)? {
+                Some(c) => c,
+                None => return Err(PCMError::MissingChunk(*b"data")),
+            };
+            let chunk_offset = reader.seek(SeekFrom::Current(0))? - 8;
+            match &chunk_id {
+                b"fmt " => {
+                    format = Some(
+                        WaveFormat::import(reader, chunk_size, endian)
+                            .map_err(|e| e.in_chunk(chunk_id, chunk_offset))?,
+                    );
+                    skip_padding(reader, u64::from(chunk_size))?;
+                }
+                b"data" => {
+                    return match format {
+                        Some(format) => Ok(WaveHeader {
+                            data_size: match ds64_data_size {
+                                Some(size) if chunk_size == UNKNOWN_DATA_SIZE => size,
+                                _ if chunk_size == UNKNOWN_DATA_SIZE => {
+                                    streamed_data_size(reader, &format)?
+                                }
+                                _ => u64::from(chunk_size),
+                            },
+                            format,
+                            metadata,
+                            data_chunk_offset: chunk_offset,
+                            riff_end,
+                        }),
+                        None => Err(PCMError::MissingChunk(*b"fmt ")),
+                    }
+                }
+                _ => metadata
+                    .import_chunk(reader, chunk_id, chunk_size, endian)
+                    .map_err(|e| e.in_chunk(chunk_id, chunk_offset))?,
+            }
+        }
+    }
+    /// Reads the ID and size of the next chunk, returns None when there are no chunks left
+    fn next_chunk<R: Read + Seek>(&self, reader: &mut R) -> Result<Option<([u8; 4], u32)>> {
+        next_chunk(reader, self.riff_end, self.format.endian)
+    }
+    /// Returns the number of frames in the audio data.
+    /// For ADPCM, the fact chunk tells how many frames are real, the last block being padded.
+    fn nb_frames(&self) -> u64 {
+        let nb_frames = self.format.nb_frames(self.data_size);
+        match (&self.format.parameters.sample_type, self.metadata.sample_length) {
+            (Sample::ImaADPCM(_), Some(l)) | (Sample::MicrosoftADPCM(_), Some(l)) => {
+                nb_frames.min(u64::from(l))
+            }
+            _ => nb_frames,
+        }
+    }
+    /// Reads and decodes all the audio data, a batch of whole blocks at a time
+    fn read_frames<R: Read>(&self, reader: &mut R) -> Result<Vec<Frame>> {
+        let block_size = (self.format.block_size() as u64).max(1);
+        let batch_size = (IMPORT_BATCH_SIZE / block_size).max(1) * block_size;
+        let mut frames = Vec::with_capacity(self.nb_frames().min(MAX_PREALLOCATION) as usize);
+        let mut remaining = self.data_size;
+        while remaining > 0 {
+            let size = batch_size.min(remaining);
+            let data = read_bytes(reader, size).map_err(|e| match e {
+                PCMError::TruncatedData { actual, .. } => PCMError::TruncatedData {
+                    expected: self.data_size,
+                    actual: self.data_size - remaining + actual,
+                },
+                e => e,
+            })?;
+            frames.extend(self.format.decode(&data)?);
+            remaining -= size;
+        }
+        Ok(frames)
+    }
+    /// Returns the loops found in the file, some software express them in bytes instead of frames
+    fn frame_loops(&self, nb_frames: usize) -> Option<Vec<LoopInfo>> {
+        let bytes_per_frame = if self.format.parameters.sample_type.is_compressed() {
+            1
+        } else {
+            self.format.block_size() as u64
+        };
+        let nb_frames = nb_frames as u64;
+        self.metadata.loop_info.as_ref().map(|loops| {
+            loops
+                .iter()
+                .map(|l| {
+                    if bytes_per_frame > 1 && l.loop_end >= nb_frames
+                        && l.loop_end / bytes_per_frame < nb_frames
+                    {
+                        LoopInfo {
+                            loop_start: l.loop_start / bytes_per_frame,
+                            loop_end: l.loop_end / bytes_per_frame,
+                        }
+                    } else {
+                        l.clone()
                     }
-                    Sample::Signed16bits(_) => {
-                        samples.push(Sample::Signed16bits(pcm_raw.read_le_to_i16()?))
+                })
+                .collect()
+        })
+    }
+}
+
+/// Reads the ID and size of the next chunk in a RIFF, returns None when there are no chunks left
+fn next_chunk<R: Read + Seek>(
+    reader: &mut R,
+    riff_end: u64,
+    endian: Endianness,
+) -> Result<Option<([u8; 4], u32)>> {
+    if reader.seek(SeekFrom::Current(0))? + 8 > riff_end {
+        return Ok(None);
+    }
+    let mut chunk_id = [0u8; 4];
+    match reader.read_exact(&mut chunk_id) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Some((chunk_id, reader.read_u32_endian(endian)?)))
+}
+
+/// Reads numbers in the byte order of a Wave file
+trait ReadEndian: Read {
+    fn read_u16_endian(&mut self, endian: Endianness) -> Result<u16> {
+        Ok(match endian {
+            Endianness::Little => self.read_le_to_u16()?,
+            Endianness::Big => self.read_be_to_u16()?,
+        })
+    }
+    fn read_i16_endian(&mut self, endian: Endianness) -> Result<i16> {
+        Ok(match endian {
+            Endianness::Little => self.read_le_to_i16()?,
+            Endianness::Big => self.read_be_to_i16()?,
+        })
+    }
+    fn read_u32_endian(&mut self, endian: Endianness) -> Result<u32> {
+        Ok(match endian {
+            Endianness::Little => self.read_le_to_u32()?,
+            Endianness::Big => self.read_be_to_u32()?,
+        })
+    }
+}
+
+impl<R: Read + ?Sized> ReadEndian for R {}
+
+/// Returns the size of audio data going on until the end of the file, without the last frame if it was cut
+fn streamed_data_size<R: Seek>(reader: &mut R, format: &WaveFormat) -> Result<u64> {
+    let data_start = reader.seek(SeekFrom::Current(0))?;
+    let data_end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(data_start))?;
+    let data_size = data_end - data_start;
+    Ok(if format.parameters.sample_type.is_compressed() {
+        data_size
+    } else {
+        data_size - data_size % (format.block_size() as u64).max(1)
+    })
+}
+
+/// Reads a text field of a fixed size, which ends at the first null byte if it is shorter
+fn read_text<R: Read>(reader: &mut R, size: u64) -> Result<String> {
+    let mut text = read_bytes(reader, size)?;
+    let text_end = text.iter().position(|c| *c == 0).unwrap_or(text.len());
+    text.truncate(text_end);
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
+
+/// Writes a text field of a fixed size, cut if too long and padded with null bytes if too short
+fn write_text<W: Write>(writer: &mut W, text: &str, size: usize) -> Result<()> {
+    let bytes = &text.as_bytes()[..text.len().min(size)];
+    writer.write_all(bytes)?;
+    writer.write_all(&vec![0u8; size - bytes.len()])?;
+    Ok(())
+}
+
+/// Skips the byte that keeps the next chunk word-aligned after a chunk of odd size
+fn skip_padding<R: Seek>(reader: &mut R, chunk_size: u64) -> Result<()> {
+    if chunk_size & 1 == 1 {
+        reader.seek(SeekFrom::Current(1))?;
+    }
+    Ok(())
+}
+
+impl WaveMetadata {
+    /// Reads a chunk that is not needed to decode the audio data, unknown chunks are skipped
+    fn import_chunk<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        chunk_id: [u8; 4],
+        chunk_size: u32,
+        endian: Endianness,
+    ) -> Result<()> {
+        let chunk_end = reader.seek(SeekFrom::Current(0))? + u64::from(chunk_size);
+        if &chunk_id == b"fact" && chunk_size >= 4 {
+            self.sample_length = Some(reader.read_u32_endian(endian)?);
+        } else if &chunk_id == b"cue " {
+            let nb_cue_points = reader.read_u32_endian(endian)?;
+            for _ in 0..nb_cue_points.min(chunk_size.saturating_sub(4) / 24) {
+                let id = reader.read_u32_endian(endian)?;
+                let _position = reader.read_u32_endian(endian)?;
+                let _data_chunk_id = reader.read_u32_endian(endian)?;
+                let _chunk_start = reader.read_u32_endian(endian)?;
+                let _block_start = reader.read_u32_endian(endian)?;
+                let sample_offset = reader.read_u32_endian(endian)?;
+                self.cue_points.push((id, u64::from(sample_offset)));
+            }
+        } else if &chunk_id == b"LIST" && chunk_size >= 4 {
+            let mut list_type = [0u8; 4];
+            reader.read_exact(&mut list_type)?;
+            if &list_type == b"adtl" {
+                // Associated Data List, only labels are kept
+                while reader.seek(SeekFrom::Current(0))? + 8 <= chunk_end {
+                    let mut sub_chunk_id = [0u8; 4];
+                    reader.read_exact(&mut sub_chunk_id)?;
+                    let sub_chunk_size = reader.read_u32_endian(endian)?;
+                    let sub_chunk_end = reader.seek(SeekFrom::Current(0))?
+                        + u64::from(sub_chunk_size)
+                        + u64::from(sub_chunk_size & 1);
+                    if &sub_chunk_id == b"labl" && sub_chunk_size >= 4 {
+                        let id = reader.read_u32_endian(endian)?;
+                        let text = read_text(reader, u64::from(sub_chunk_size - 4))?;
+                        self.labels.push((id, text));
                     }
-                    _ => unimplemented!("Cannot read anything else than u8 and i16 for now"),
+                    reader.seek(SeekFrom::Start(sub_chunk_end))?;
+                }
+            } else if &list_type == b"INFO" {
+                // Text tags, each sub-chunk holding a null-terminated string
+                while reader.seek(SeekFrom::Current(0))? + 8 <= chunk_end {
+                    let mut sub_chunk_id = [0u8; 4];
+                    reader.read_exact(&mut sub_chunk_id)?;
+                    let sub_chunk_size = reader.read_u32_endian(endian)?;
+                    let sub_chunk_end = reader.seek(SeekFrom::Current(0))?
+                        + u64::from(sub_chunk_size)
+                        + u64::from(sub_chunk_size & 1);
+                    let text = read_text(reader, u64::from(sub_chunk_size))?;
+                    self.tags.insert(sub_chunk_id, text);
+                    reader.seek(SeekFrom::Start(sub_chunk_end))?;
                 }
             }
-            frames.push(Frame { samples });
+        } else if &chunk_id == b"bext" && chunk_size >= BEXT_FIXED_SIZE {
+            let description = read_text(reader, 256)?;
+            let originator = read_text(reader, 32)?;
+            let originator_reference = read_text(reader, 32)?;
+            let origination_date = read_text(reader, 10)?;
+            let origination_time = read_text(reader, 8)?;
+            let time_reference_low = reader.read_u32_endian(endian)?;
+            let time_reference_high = reader.read_u32_endian(endian)?;
+            let version = reader.read_u16_endian(endian)?;
+            let mut umid = [0u8; 64];
+            reader.read_exact(&mut umid)?;
+            let mut loudness = [0i16; 5];
+            for value in &mut loudness {
+                *value = reader.read_i16_endian(endian)?;
+            }
+            let _reserved = read_bytes(reader, 180)?;
+            let coding_history = read_text(reader, u64::from(chunk_size - BEXT_FIXED_SIZE))?;
+            self.broadcast_info = Some(BroadcastInfo {
+                description,
+                originator,
+                originator_reference,
+                origination_date,
+                origination_time,
+                time_reference: u64::from(time_reference_high) << 32
+                    | u64::from(time_reference_low),
+                version,
+                umid,
+                loudness,
+                coding_history,
+            });
+        } else if &chunk_id == b"PEAK" && chunk_size >= 8 {
+            let _version = reader.read_u32_endian(endian)?;
+            let _time_stamp = reader.read_u32_endian(endian)?;
+            let nb_peaks = (chunk_size - 8) / 8;
+            // There is one peak per channel, the chunk size cannot be trusted more than that
+            let mut peaks =
+                Vec::with_capacity(nb_peaks.min(u32::from(<u16>::max_value())) as usize);
+            for _ in 0..nb_peaks {
+                let value = f32::from_bits(reader.read_u32_endian(endian)?);
+                let position = u64::from(reader.read_u32_endian(endian)?);
+                peaks.push(ChannelPeak { value, position });
+            }
+            self.peaks = Some(peaks);
+        } else if &chunk_id == b"smpl" {
+            let _manufacturer = reader.read_u32_endian(endian)?;
+            let _product = reader.read_u32_endian(endian)?;
+            let _sample_period = reader.read_u32_endian(endian)?;
+            let _midi_unity_note = reader.read_u32_endian(endian)?;
+            let _midi_pitch_fraction = reader.read_u32_endian(endian)?;
+            let _smpte_format = reader.read_u32_endian(endian)?;
+            let _smpte_offset = reader.read_u32_endian(endian)?;
+            let nb_loops = reader.read_u32_endian(endian)?;
+            let _sampler_data_size = reader.read_u32_endian(endian)?;
+            let nb_loops = nb_loops.min(chunk_size.saturating_sub(36) / 24);
+            // The chunk size may be made up, memory is only taken as loops are read
+            let max_capacity = MAX_PREALLOCATION / mem::size_of::<LoopInfo>() as u64;
+            let mut loop_info = Vec::with_capacity(u64::from(nb_loops).min(max_capacity) as usize);
+            for _ in 0..nb_loops {
+                let _identifier = reader.read_u32_endian(endian)?;
+                let _loop_type = reader.read_u32_endian(endian)?;
+                let loop_start = u64::from(reader.read_u32_endian(endian)?);
+                let loop_end = u64::from(reader.read_u32_endian(endian)?);
+                let _fraction = reader.read_u32_endian(endian)?;
+                let _play_count = reader.read_u32_endian(endian)?;
+                loop_info.push(LoopInfo {
+                    loop_start,
+                    loop_end,
+                });
+            }
+            self.loop_info = Some(loop_info);
         }
-        Ok(PCM {
+        // Chunks are word-aligned
+        reader.seek(SeekFrom::Start(chunk_end + u64::from(chunk_size & 1)))?;
+        Ok(())
+    }
+}
+
+impl WaveMetadata {
+    /// Returns the cue points along with their labels
+    fn markers(&self) -> Vec<CuePoint> {
+        self.cue_points
+            .iter()
+            .map(|(id, position)| CuePoint {
+                id: *id,
+                position: *position,
+                label: self.labels
+                    .iter()
+                    .find(|(label_id, _)| label_id == id)
+                    .map(|(_, text)| text.clone()),
+            })
+            .collect()
+    }
+}
+
+impl WaveFormat {
+    /// Reads the contents of a format chunk
+    fn import<R: Read>(reader: &mut R, chunk_size: u32, endian: Endianness) -> Result<WaveFormat> {
+        // Reading the fixed part of a shorter chunk would go into the next one
+        if chunk_size < 16 {
+            return Err(PCMError::InvalidHeader("a format chunk smaller than 16 bytes"));
+        }
+        let mut audio_format = reader.read_u16_endian(endian)?;
+        let nb_channels = reader.read_u16_endian(endian)?;
+        let sample_rate = reader.read_u32_endian(endian)?;
+        let _byte_rate = reader.read_u32_endian(endian)?;
+        let block_align = reader.read_u16_endian(endian)?;
+        let bits_per_sample = reader.read_u16_endian(endian)?;
+        // Sizes of the audio data are computed from these
+        if nb_channels == 0 {
+            return Err(PCMError::InvalidHeader("0 channels"));
+        }
+        if sample_rate == 0 {
+            return Err(PCMError::InvalidHeader("a sample rate of 0"));
+        }
+        if bits_per_sample == 0 {
+            return Err(PCMError::InvalidHeader("0 bits per sample"));
+        }
+        // Extra format information
+        let format_extra = read_bytes(reader, u64::from(chunk_size.saturating_sub(16)))?;
+        let mut format_extra = Cursor::new(format_extra);
+        let mut channel_mask = None;
+        if audio_format == 0xFFFE {
+            // WAVE_FORMAT_EXTENSIBLE, the actual format is in the Sub Format GUID
+            let _extra_size = format_extra.read_u16_endian(endian)?;
+            let _valid_bits_per_sample = format_extra.read_u16_endian(endian)?;
+            channel_mask = Some(format_extra.read_u32_endian(endian)?);
+            let mut sub_format = [0u8; 16];
+            format_extra.read_exact(&mut sub_format)?;
+            if endian == Endianness::Big {
+                // The first three fields of a GUID are numbers
+                sub_format[..4].reverse();
+                sub_format[4..6].reverse();
+                sub_format[6..8].reverse();
+            }
+            if sub_format[2..] != EXTENSIBLE_SUB_FORMAT_SUFFIX {
+                return Err(PCMError::UnknownFormat(audio_format));
+            }
+            audio_format = u16::from(sub_format[0]) | (u16::from(sub_format[1]) << 8);
+        }
+        let sample_type = Sample::wave_from_format_bps(&audio_format, &bits_per_sample)?;
+        // ADPCM blocks are only defined in little-endian
+        if sample_type.is_compressed() && endian == Endianness::Big {
+            return Err(PCMError::UnsupportedSampleType(sample_type));
+        }
+        if sample_type.is_compressed() && block_align == 0 {
+            return Err(PCMError::InvalidHeader("ADPCM blocks of 0 bytes"));
+        }
+        let mut coefficients = Vec::new();
+        if let Sample::MicrosoftADPCM(_) = sample_type {
+            let _extra_size = format_extra.read_u16_endian(endian)?;
+            let _samples_per_block = format_extra.read_u16_endian(endian)?;
+            let nb_coefficients = format_extra.read_u16_endian(endian)?;
+            for _ in 0..nb_coefficients {
+                coefficients.push((
+                    format_extra.read_i16_endian(endian)?,
+                    format_extra.read_i16_endian(endian)?,
+                ));
+            }
+        }
+        let mut parameters = PCMParameters::new(sample_rate, nb_channels, sample_type)?;
+        parameters.channel_mask = channel_mask;
+        Ok(WaveFormat {
             parameters,
-            loop_info: None,
+            endian,
+            block_align,
+            coefficients,
+        })
+    }
+    /// Returns the smallest amount of bytes that can be decoded on its own
+    fn block_size(&self) -> usize {
+        if self.parameters.sample_type.is_compressed() {
+            self.block_align as usize
+        } else {
+            self.parameters.frame_size()
+        }
+    }
+    /// Returns how many frames are stored in a number of bytes of audio data
+    fn nb_frames(&self, data_size: u64) -> u64 {
+        let nb_channels = u64::from(self.parameters.nb_channels);
+        let block_size = self.block_size() as u64;
+        if block_size == 0 || nb_channels == 0 {
+            return 0;
+        }
+        // Frames in a block of ADPCM data of a given size, some being stored in the block header
+        let adpcm_frames = |size: u64| match self.parameters.sample_type {
+            Sample::ImaADPCM(_) if size >= 4 * nb_channels => {
+                (size - 4 * nb_channels) * 2 / nb_channels + 1
+            }
+            Sample::MicrosoftADPCM(_) if size >= 7 * nb_channels => {
+                (size - 7 * nb_channels) * 2 / nb_channels + 2
+            }
+            _ => 0,
+        };
+        match self.parameters.sample_type {
+            Sample::ImaADPCM(_) | Sample::MicrosoftADPCM(_) => {
+                data_size / block_size * adpcm_frames(block_size)
+                    + adpcm_frames(data_size % block_size)
+            }
+            _ => data_size / block_size,
+        }
+    }
+    /// Decodes audio data into frames
+    fn decode(&self, data: &[u8]) -> Result<Vec<Frame>> {
+        let nb_channels = self.parameters.nb_channels;
+        Ok(match self.parameters.sample_type {
+            Sample::ImaADPCM(_) => ima_adpcm::decode(data, nb_channels, self.block_align)?,
+            Sample::MicrosoftADPCM(_) => {
+                microsoft_adpcm::decode(data, nb_channels, self.block_align, &self.coefficients)?
+            }
+            _ => {
+                // Reading from a slice moves it forward, a trailing partial frame is left out
+                let mut pcm_raw = data;
+                let nb_frames = data.len() / self.block_size();
+                let mut frames = Vec::with_capacity(nb_frames);
+                for _ in 0..nb_frames {
+                    let mut samples = Vec::with_capacity(nb_channels as usize);
+                    for _ in 0..nb_channels {
+                        samples.push(
+                            self.parameters
+                                .sample_type
+                                .read_raw(&mut pcm_raw, self.endian)?,
+                        );
+                    }
+                    frames.push(Frame { samples });
+                }
+                frames
+            }
+        })
+    }
+}
+
+/// Reads frames from a Wave file one at a time instead of loading the whole file in memory
+pub struct WaveFrameReader<R: Read + Seek> {
+    reader: R,
+    header: WaveHeader,
+    remaining_data: u64,
+    remaining_frames: u64,
+    pending_frames: VecDeque<Frame>,
+}
+
+impl<R: Read + Seek> WaveFrameReader<R> {
+    /// Reads the header of a Wave file and gets ready to read frames
+    pub fn new(mut reader: R) -> Result<WaveFrameReader<R>> {
+        let header = WaveHeader::import(&mut reader)?;
+        Ok(WaveFrameReader {
+            reader,
+            remaining_data: header.data_size,
+            remaining_frames: header.nb_frames(),
+            header,
+            pending_frames: VecDeque::new(),
+        })
+    }
+    /// Returns the parameters of the signal being read
+    pub fn parameters(&self) -> &PCMParameters {
+        &self.header.format.parameters
+    }
+    /// Reads and decodes the next frame, returns None once all the audio data has been read
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        if self.remaining_frames == 0 {
+            return Ok(None);
+        }
+        while self.pending_frames.is_empty() {
+            if self.remaining_data == 0 {
+                return Ok(None);
+            }
+            // ADPCM frames can only be decoded a block at a time
+            let size = (self.header.format.block_size() as u64).min(self.remaining_data);
+            let mut data = vec![0u8; size as usize];
+            self.reader.read_exact(&mut data)?;
+            self.remaining_data -= size;
+            self.pending_frames.extend(self.header.format.decode(&data)?);
+        }
+        self.remaining_frames -= 1;
+        Ok(self.pending_frames.pop_front())
+    }
+}
+
+impl<R: Read + Seek> Iterator for WaveFrameReader<R> {
+    type Item = Result<Frame>;
+    fn next(&mut self) -> Option<Result<Frame>> {
+        match self.next_frame() {
+            Ok(Some(f)) => Some(Ok(f)),
+            Ok(None) => None,
+            Err(e) => {
+                // Do not keep on reading garbage after an error
+                self.remaining_data = 0;
+                self.pending_frames.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl PCM {
+    /// Imports a Wave file and returns a corresponding PCM, big-endian RIFX files are also accepted.
+    /// The PEAK chunk is left out, probe_wave gives it.
+    pub fn wave_import_file<R: Read + Seek>(reader: &mut R) -> Result<PCM> {
+        let header = WaveHeader::import(reader)?;
+        let data_chunk_offset = header.data_chunk_offset;
+        let frames = header
+            .read_frames(reader)
+            .map_err(|e| e.in_chunk(*b"data", data_chunk_offset))?;
+        PCM::wave_import_end(reader, header, frames)
+    }
+    #[cfg(feature = "rayon")]
+    /// Same as wave_import_file, but the audio data is loaded in memory and decoded on all cores
+    pub fn wave_import_file_parallel<R: Read + Seek>(reader: &mut R) -> Result<PCM> {
+        let header = WaveHeader::import(reader)?;
+        let data_chunk_offset = header.data_chunk_offset;
+        let block_size = (header.format.block_size() as u64).max(1);
+        let batch_size = (IMPORT_BATCH_SIZE / block_size).max(1) * block_size;
+        let data = read_bytes(reader, header.data_size)
+            .map_err(|e| e.in_chunk(*b"data", data_chunk_offset))?;
+        let batches = data
+            .par_chunks(batch_size as usize)
+            .map(|batch| header.format.decode(batch))
+            .collect::<Result<Vec<Vec<Frame>>>>()
+            .map_err(|e| e.in_chunk(*b"data", data_chunk_offset))?;
+        let frames = batches.into_iter().flatten().collect();
+        PCM::wave_import_end(reader, header, frames)
+    }
+    /// Reads the chunks after the audio data and puts the PCM together
+    fn wave_import_end<R: Read + Seek>(
+        reader: &mut R,
+        mut header: WaveHeader,
+        mut frames: Vec<Frame>,
+    ) -> Result<PCM> {
+        frames.truncate(header.nb_frames() as usize);
+        skip_padding(reader, header.data_size)?;
+        // Chunks after the audio data
+        while let Some((chunk_id, chunk_size)) = header.next_chunk(reader)? {
+            let chunk_offset = reader.seek(SeekFrom::Current(0))? - 8;
+            header
+                .metadata
+                .import_chunk(reader, chunk_id, chunk_size, header.format.endian)
+                .map_err(|e| e.in_chunk(chunk_id, chunk_offset))?;
+        }
+        Ok(PCM {
+            loop_info: header.frame_loops(frames.len()),
+            parameters: header.format.parameters,
+            markers: header.metadata.markers(),
+            broadcast_info: header.metadata.broadcast_info,
+            tags: header.metadata.tags,
             frames,
         })
     }
+    /// Imports a Wave file held in memory
+    pub fn from_wave_bytes(bytes: &[u8]) -> Result<PCM> {
+        PCM::wave_import_file(&mut Cursor::new(bytes))
+    }
+    /// Reads the header of a Wave file without decoding the audio data, leaving the reader after it
+    pub fn probe_wave<R: Read + Seek>(reader: &mut R) -> Result<WaveInfo> {
+        let header = WaveHeader::import(reader)?;
+        reader.seek(SeekFrom::Current(header.data_size as i64))?;
+        skip_padding(reader, header.data_size)?;
+        Ok(WaveInfo {
+            nb_frames: header.nb_frames(),
+            data_offset: header.data_chunk_offset + 8,
+            data_size: header.data_size,
+            block_align: header.format.block_align,
+            peaks: header.metadata.peaks,
+            parameters: header.format.parameters,
+        })
+    }
     /// Exports a Wave file from a PCM
     pub fn wave_export_file<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
-        // Check if the audio size can fit into a Wave file
-        if self.get_audio_size() > (<u32>::max_value() as usize) {
-            return Err(PCMError::TooMuchData(self.get_audio_size()));
+        self.wave_export(
+            writer,
+            self.parameters
+                .sample_type
+                .wave_get_block_align(self.parameters.nb_channels),
+            false,
+        )
+    }
+    /// Exports a Wave file into memory
+    pub fn to_wave_bytes(&self) -> Result<Vec<u8>> {
+        let mut writer = Cursor::new(Vec::with_capacity(self.get_audio_size()));
+        self.wave_export_file(&mut writer)?;
+        Ok(writer.into_inner())
+    }
+    /// Exports a Wave file from an IMA ADPCM PCM, with the size in bytes of the encoded blocks
+    pub fn wave_export_file_adpcm<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        block_align: u16,
+    ) -> Result<()> {
+        if let Sample::ImaADPCM(_) = self.parameters.sample_type {
+            ima_adpcm::check_block_align(block_align, self.parameters.nb_channels)?;
+            self.wave_export(writer, block_align, false)
+        } else {
+            Err(PCMError::UnsupportedSampleType(
+                self.parameters.sample_type.clone(),
+            ))
         }
-        if self.parameters
-            .sample_type
-            .wave_get_format_chunk_extra_size() != 0
+    }
+    /// Exports a Wave file with the block align written in the format chunk, which is also the size of ADPCM blocks.
+    /// An RF64 file is written when the sizes do not fit in 32 bits, or if rf64 is true.
+    fn wave_export<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        block_align: u16,
+        rf64: bool,
+    ) -> Result<()> {
+        let (header, trailer) = self.wave_chunks(block_align, rf64)?;
+        writer.write_all(&header)?;
+        self.wave_write_data(writer, block_align)?;
+        writer.write_all(&trailer)?;
+        Ok(())
+    }
+    /// Writes the audio data of a Wave file, encoded if needed
+    fn wave_write_data<W: Write>(&self, writer: &mut W, block_align: u16) -> Result<()> {
+        if let Sample::ImaADPCM(_) = self.parameters.sample_type {
+            ima_adpcm::encode(writer, &self.frames, self.parameters.nb_channels, block_align)
+        } else {
+            self.write_interleaved(writer, Endianness::Little)
+        }
+    }
+    /// Returns the chunks before the audio data, up to the header of the data chunk, and the ones after it
+    fn wave_chunks(&self, block_align: u16, rf64: bool) -> Result<(Vec<u8>, Vec<u8>)> {
+        self.validate()?;
+        // The sampler chunk stores the period of a sample
+        if self.parameters.sample_rate == 0 {
+            return Err(PCMError::InvalidParameters("a sample rate of 0"));
+        }
+        // The block align and the byte rate of the format chunk must fit
+        if self.parameters.frame_size() > usize::from(<u16>::max_value())
+            || self.parameters.frame_size() as u64 * u64::from(self.parameters.sample_rate)
+                > u64::from(<u32>::max_value())
         {
-            unimplemented!(
-                "Cannot work with sample types that requires extra info in format chunk for now"
-            );
+            return Err(PCMError::InvalidParameters("frames too big for a Wave file"));
+        }
+        let is_ima_adpcm = matches!(self.parameters.sample_type, Sample::ImaADPCM(_));
+        let audio_size = if is_ima_adpcm {
+            ima_adpcm::encoded_size(self.frames.len(), self.parameters.nb_channels, block_align)?
+        } else {
+            self.get_audio_size()
+        };
+        // Microsoft ADPCM cannot be encoded
+        if let Sample::MicrosoftADPCM(_) = self.parameters.sample_type {
+            return Err(PCMError::UnsupportedSampleType(
+                self.parameters.sample_type.clone(),
+            ));
         }
+        // Multichannel audio needs WAVE_FORMAT_EXTENSIBLE to tell which channel goes where
+        let is_extensible = (self.parameters.nb_channels > 2
+            || self.parameters.channel_mask.is_some())
+            && self.parameters
+                .sample_type
+                .wave_get_format_chunk_extra_size() == 0;
         // Calculate sizes of all chunks beforehand
-        let format_chunk_size_interior = 16 + self.parameters
-            .sample_type
-            .wave_get_format_chunk_extra_size();
+        let format_chunk_size_interior = 16 + if is_extensible {
+            24
+        } else {
+            self.parameters
+                .sample_type
+                .wave_get_format_chunk_extra_size()
+        };
         let format_chunk_size_total = format_chunk_size_interior + 8;
         let (fact_chunk_size_interior, fact_chunk_size_total) =
             if self.parameters.sample_type.wave_get_best_format() == 1 {
@@ -81,39 +899,267 @@ impl PCM {
             } else {
                 (4, 12)
             };
-        let data_chunk_size_interior = self.get_audio_size() as u32;
-        let data_chunk_size_total = data_chunk_size_interior + 8;
-        let riff_chunk_size_interior =
-            format_chunk_size_total + fact_chunk_size_total + data_chunk_size_total;
+        // Float files carry the peak of each channel
+        let peak_chunk_size_interior = if self.parameters.sample_type.is_float() {
+            8 + 8 * u32::from(self.parameters.nb_channels)
+        } else {
+            0
+        };
+        let peak_chunk_size_total = if peak_chunk_size_interior == 0 {
+            0
+        } else {
+            peak_chunk_size_interior + 8
+        };
+        let data_chunk_size_interior = audio_size as u64;
+        let data_chunk_size_total = data_chunk_size_interior + 8 + (data_chunk_size_interior & 1);
+        let loops = match self.loop_info {
+            Some(ref l) => &l[..],
+            None => &[],
+        };
+        let (sampler_chunk_size_interior, sampler_chunk_size_total) = if loops.is_empty() {
+            (0, 0)
+        } else {
+            (36 + 24 * loops.len() as u32, 44 + 24 * loops.len() as u32)
+        };
+        let cue_chunk_size_interior = 4 + 24 * self.markers.len() as u32;
+        let cue_chunk_size_total = if self.markers.is_empty() {
+            0
+        } else {
+            cue_chunk_size_interior + 8
+        };
+        // Labels are null-terminated and padded to an even size
+        let label_sizes: Vec<(u32, &String)> = self.markers
+            .iter()
+            .filter_map(|m| m.label.as_ref().map(|l| (4 + l.len() as u32 + 1, l)))
+            .collect();
+        let list_chunk_size_interior =
+            4 + label_sizes.iter().map(|(s, _)| 8 + s + (s & 1)).sum::<u32>();
+        let list_chunk_size_total = if label_sizes.is_empty() {
+            0
+        } else {
+            list_chunk_size_interior + 8
+        };
+        // Tags are null-terminated and padded to an even size as well
+        let info_chunk_size_interior = 4 + self
+            .tags
+            .values()
+            .map(|t| {
+                let size = t.len() as u32 + 1;
+                8 + size + (size & 1)
+            })
+            .sum::<u32>();
+        let info_chunk_size_total = if self.tags.is_empty() {
+            0
+        } else {
+            info_chunk_size_interior + 8
+        };
+        let bext_chunk_size_interior = match self.broadcast_info {
+            Some(ref b) => BEXT_FIXED_SIZE + b.coding_history.len() as u32,
+            None => 0,
+        };
+        let bext_chunk_size_total = if self.broadcast_info.is_some() {
+            bext_chunk_size_interior + 8 + (bext_chunk_size_interior & 1)
+        } else {
+            0
+        };
+        let mut riff_chunk_size_interior = 4
+            + u64::from(
+                bext_chunk_size_total
+                    + format_chunk_size_total
+                    + fact_chunk_size_total
+                    + peak_chunk_size_total
+                    + sampler_chunk_size_total
+                    + cue_chunk_size_total
+                    + list_chunk_size_total
+                    + info_chunk_size_total,
+            )
+            + data_chunk_size_total;
+        // RF64 files give the sizes that do not fit in 32 bits in a ds64 chunk
+        let is_rf64 = rf64 || riff_chunk_size_interior > u64::from(<u32>::max_value());
+        if is_rf64 {
+            riff_chunk_size_interior += 8 + DS64_SIZE;
+        }
         // Write the header
-        writer.write_all(&[b'R', b'I', b'F', b'F'])?; // RIFF Chunk
-        writer.write_le_to_u32(riff_chunk_size_interior)?; // Interior Size of RIFF Chunk
-        writer.write_all(&[b'W', b'A', b'V', b'E'])?; // WAVE Format
+        let mut writer = Vec::new();
+        if is_rf64 {
+            writer.write_all(&[b'R', b'F', b'6', b'4'])?; // RF64 Chunk
+            writer.write_le_to_u32(<u32>::max_value())?; // Interior Size of RF64 Chunk, in the ds64 chunk
+            writer.write_all(&[b'W', b'A', b'V', b'E'])?; // WAVE Format
+            writer.write_all(&[b'd', b's', b'6', b'4'])?; // Data Size 64 chunk
+            writer.write_le_to_u32(DS64_SIZE as u32)?; // Data Size 64 chunk interior size
+            writer.write_le_to_u64(riff_chunk_size_interior)?; // Interior Size of RF64 Chunk
+            writer.write_le_to_u64(data_chunk_size_interior)?; // Data chunk interior size
+            writer.write_le_to_u64(self.frames.len() as u64)?; // Number of frames
+            writer.write_le_to_u32(0)?; // Table Length, no other chunk is that big
+        } else {
+            writer.write_all(&[b'R', b'I', b'F', b'F'])?; // RIFF Chunk
+            writer.write_le_to_u32(riff_chunk_size_interior as u32)?; // Interior Size of RIFF Chunk
+            writer.write_all(&[b'W', b'A', b'V', b'E'])?; // WAVE Format
+        }
+        if let Some(ref broadcast_info) = self.broadcast_info {
+            writer.write_all(&[b'b', b'e', b'x', b't'])?; // Broadcast Extension chunk
+            writer.write_le_to_u32(bext_chunk_size_interior)?; // Broadcast Extension chunk interior size
+            write_text(&mut writer, &broadcast_info.description, 256)?; // Description
+            write_text(&mut writer, &broadcast_info.originator, 32)?; // Originator
+            write_text(&mut writer, &broadcast_info.originator_reference, 32)?; // Originator Reference
+            write_text(&mut writer, &broadcast_info.origination_date, 10)?; // Origination Date
+            write_text(&mut writer, &broadcast_info.origination_time, 8)?; // Origination Time
+            writer.write_le_to_u32(broadcast_info.time_reference as u32)?; // Time Reference, low part
+            writer.write_le_to_u32((broadcast_info.time_reference >> 32) as u32)?; // Time Reference, high part
+            writer.write_le_to_u16(broadcast_info.version)?; // Version
+            writer.write_all(&broadcast_info.umid)?; // UMID
+            for value in &broadcast_info.loudness {
+                writer.write_le_to_i16(*value)?; // Loudness values
+            }
+            writer.write_all(&[0u8; 180])?; // Reserved
+            writer.write_all(broadcast_info.coding_history.as_bytes())?; // Coding History
+            if bext_chunk_size_interior & 1 == 1 {
+                writer.write_to_u8(0)?; // Padding
+            }
+        }
         writer.write_all(&[b'f', b'm', b't', b' '])?; // Format Chunk
         writer.write_le_to_u32(format_chunk_size_interior)?; // Format Chunk interior size
-        writer.write_le_to_u16(self.parameters.sample_type.wave_get_best_format())?; // Audio Format
+        if is_extensible {
+            writer.write_le_to_u16(0xFFFE)?; // Audio Format, actual format is in the extra info
+        } else {
+            writer.write_le_to_u16(self.parameters.sample_type.wave_get_best_format())?; // Audio Format
+        }
         writer.write_le_to_u16(self.parameters.nb_channels)?; // Number of Channels
         writer.write_le_to_u32(self.parameters.sample_rate)?; // Sample Rate
-        writer.write_le_to_u32(
-            self.parameters.sample_rate * u32::from(self.parameters.nb_channels)
-                * (u32::from(self.parameters.sample_type.get_binary_size() / 8)),
-        )?; // Byte Rate
-        writer.write_le_to_u16(
-            self.parameters.nb_channels * (self.parameters.sample_type.get_binary_size() / 8),
-        )?; // Block Align
-        writer.write_le_to_u16(self.parameters.sample_type.get_binary_size())?; // Bits per Sample
+        if is_ima_adpcm {
+            let samples_per_block =
+                ima_adpcm::samples_per_block(block_align, self.parameters.nb_channels)?;
+            writer.write_le_to_u32(
+                (u64::from(self.parameters.sample_rate) * u64::from(block_align)
+                    / u64::from(samples_per_block)) as u32,
+            )?; // Byte Rate
+            writer.write_le_to_u16(block_align)?; // Block Align
+            writer.write_le_to_u16(self.parameters.sample_type.get_binary_size())?; // Bits per Sample
+            writer.write_le_to_u16(2)?; // Size of the extra info
+            writer.write_le_to_u16(samples_per_block)?; // Samples per Block
+        } else {
+            writer.write_le_to_u32(self.parameters.byte_rate())?; // Byte Rate
+            writer.write_le_to_u16(block_align)?; // Block Align
+            writer.write_le_to_u16(self.parameters.sample_type.get_binary_size())?; // Bits per Sample
+            if self.parameters.sample_type.wave_get_format_chunk_extra_size() == 2 {
+                writer.write_le_to_u16(0)?; // Size of the extra info, there is none
+            }
+        }
+        if is_extensible {
+            writer.write_le_to_u16(22)?; // Size of the extra info
+            writer.write_le_to_u16(self.parameters.sample_type.get_binary_size())?; // Valid Bits per Sample
+            writer.write_le_to_u32(self.parameters.get_channel_mask())?; // Channel Mask
+            writer.write_le_to_u16(self.parameters.sample_type.wave_get_best_format())?; // Sub Format
+            writer.write_all(&EXTENSIBLE_SUB_FORMAT_SUFFIX)?;
+        }
         if self.parameters.sample_type.wave_get_best_format() != 1 {
             writer.write_all(&[b'f', b'a', b'c', b't'])?; // Fact chunk
             writer.write_le_to_u32(fact_chunk_size_interior)?; // Fixed size of 4 bytes
-            if self.frames.len() > (<u32>::max_value() as usize) {
+            if is_rf64 {
+                writer.write_le_to_u32(<u32>::max_value())?; // Number of frames, in the ds64 chunk
+            } else if self.frames.len() > (<u32>::max_value() as usize) {
                 return Err(PCMError::TooManyFrames(self.frames.len()));
+            } else {
+                writer.write_le_to_u32(self.frames.len() as u32)?; // Number of frames
+            }
+        }
+        if peak_chunk_size_interior != 0 {
+            writer.write_all(&[b'P', b'E', b'A', b'K'])?; // Peak chunk
+            writer.write_le_to_u32(peak_chunk_size_interior)?; // Peak chunk interior size
+            writer.write_le_to_u32(1)?; // Version
+            writer.write_le_to_u32(0)?; // Time Stamp, unknown
+            for (_, position, value) in self.peak_positions() {
+                writer.write_le_to_f32(value.abs() as f32)?; // Peak value
+                writer.write_le_to_u32(position.min(u64::from(<u32>::max_value())) as u32)?; // Frame of the peak
             }
-            writer.write_le_to_u32(self.frames.len() as u32)?; // Number of frames
         }
         writer.write_all(&[b'd', b'a', b't', b'a'])?; // Sub-chunk 2 ID
-        writer.write_le_to_u32(data_chunk_size_interior)?; // Sub-chunk 2 size
-        self.export_raw_file(writer)?; // PCM data
-        Ok(())
+        if is_rf64 {
+            writer.write_le_to_u32(<u32>::max_value())?; // Sub-chunk 2 size, in the ds64 chunk
+        } else {
+            writer.write_le_to_u32(data_chunk_size_interior as u32)?; // Sub-chunk 2 size
+        }
+        let header = writer;
+        // Write the chunks after the audio data
+        let mut writer = Vec::new();
+        if data_chunk_size_interior & 1 == 1 {
+            writer.write_to_u8(0)?; // Padding to keep chunks word-aligned
+        }
+        if !loops.is_empty() {
+            writer.write_all(&[b's', b'm', b'p', b'l'])?; // Sampler chunk
+            writer.write_le_to_u32(sampler_chunk_size_interior)?; // Sampler chunk interior size
+            writer.write_le_to_u32(0)?; // Manufacturer
+            writer.write_le_to_u32(0)?; // Product
+            writer.write_le_to_u32(1_000_000_000 / self.parameters.sample_rate)?; // Sample Period in nanoseconds
+            writer.write_le_to_u32(60)?; // MIDI Unity Note
+            writer.write_le_to_u32(0)?; // MIDI Pitch Fraction
+            writer.write_le_to_u32(0)?; // SMPTE Format
+            writer.write_le_to_u32(0)?; // SMPTE Offset
+            writer.write_le_to_u32(loops.len() as u32)?; // Number of Sample Loops
+            writer.write_le_to_u32(0)?; // Sampler Data size
+            for (id, loop_info) in loops.iter().enumerate() {
+                if loop_info.loop_end > u64::from(<u32>::max_value()) {
+                    return Err(PCMError::TooManyFrames(loop_info.loop_end as usize));
+                }
+                writer.write_le_to_u32(id as u32)?; // Identifier
+                writer.write_le_to_u32(0)?; // Type, forward loop
+                writer.write_le_to_u32(loop_info.loop_start as u32)?; // Start
+                writer.write_le_to_u32(loop_info.loop_end as u32)?; // End
+                writer.write_le_to_u32(0)?; // Fraction
+                writer.write_le_to_u32(0)?; // Play Count, infinite
+            }
+        }
+        if !self.markers.is_empty() {
+            writer.write_all(&[b'c', b'u', b'e', b' '])?; // Cue chunk
+            writer.write_le_to_u32(cue_chunk_size_interior)?; // Cue chunk interior size
+            writer.write_le_to_u32(self.markers.len() as u32)?; // Number of Cue Points
+            for marker in &self.markers {
+                if marker.position > u64::from(<u32>::max_value()) {
+                    return Err(PCMError::TooManyFrames(marker.position as usize));
+                }
+                writer.write_le_to_u32(marker.id)?; // Identifier
+                writer.write_le_to_u32(marker.position as u32)?; // Play order position
+                writer.write_all(&[b'd', b'a', b't', b'a'])?; // Chunk containing the cue point
+                writer.write_le_to_u32(0)?; // Chunk Start
+                writer.write_le_to_u32(0)?; // Block Start
+                writer.write_le_to_u32(marker.position as u32)?; // Sample Offset
+            }
+        }
+        if !label_sizes.is_empty() {
+            writer.write_all(&[b'L', b'I', b'S', b'T'])?; // List chunk
+            writer.write_le_to_u32(list_chunk_size_interior)?; // List chunk interior size
+            writer.write_all(&[b'a', b'd', b't', b'l'])?; // Associated Data List
+            for (marker, (size, label)) in self.markers
+                .iter()
+                .filter(|m| m.label.is_some())
+                .zip(label_sizes.iter())
+            {
+                writer.write_all(&[b'l', b'a', b'b', b'l'])?; // Label chunk
+                writer.write_le_to_u32(*size)?; // Label chunk interior size
+                writer.write_le_to_u32(marker.id)?; // Cue Point Identifier
+                writer.write_all(label.as_bytes())?; // Text
+                writer.write_to_u8(0)?; // Null terminator
+                if size & 1 == 1 {
+                    writer.write_to_u8(0)?; // Padding
+                }
+            }
+        }
+        if !self.tags.is_empty() {
+            writer.write_all(&[b'L', b'I', b'S', b'T'])?; // List chunk
+            writer.write_le_to_u32(info_chunk_size_interior)?; // List chunk interior size
+            writer.write_all(&[b'I', b'N', b'F', b'O'])?; // Information List
+            for (id, text) in &self.tags {
+                let size = text.len() as u32 + 1;
+                writer.write_all(id)?; // Tag chunk
+                writer.write_le_to_u32(size)?; // Tag chunk interior size
+                writer.write_all(text.as_bytes())?; // Text
+                writer.write_to_u8(0)?; // Null terminator
+                if size & 1 == 1 {
+                    writer.write_to_u8(0)?; // Padding
+                }
+            }
+        }
+        Ok((header, writer))
     }
 }
 
@@ -126,7 +1172,7 @@ impl Sample {
                 match bits_per_sample {
                     8 => Sample::Unsigned8bits(0u8),
                     16 => Sample::Signed16bits(0i16),
-                    // 24 => Sample::Signed24bits(I24 {}), Unusable for now
+                    24 => Sample::Signed24bits(I24 { value: 0 }),
                     32 => Sample::Signed32bits(0i32),
                     x => return Err(PCMError::UnknownBitsPerSample(*x)),
                 }
@@ -134,7 +1180,7 @@ impl Sample {
             2 => {
                 // Microsoft ADPCM
                 match bits_per_sample {
-                    // 4 => Sample::MicrosoftADPCM(MicrosoftADPCM {}), Unusable for now
+                    4 => Sample::MicrosoftADPCM(MicrosoftADPCM {}),
                     x => return Err(PCMError::UnknownBitsPerSample(*x)),
                 }
             }
@@ -146,10 +1192,24 @@ impl Sample {
                     x => return Err(PCMError::UnknownBitsPerSample(*x)),
                 }
             }
+            6 => {
+                // A-law
+                match bits_per_sample {
+                    8 => Sample::ALaw(ALaw {}),
+                    x => return Err(PCMError::UnknownBitsPerSample(*x)),
+                }
+            }
+            7 => {
+                // µ-law
+                match bits_per_sample {
+                    8 => Sample::MuLaw(MuLaw {}),
+                    x => return Err(PCMError::UnknownBitsPerSample(*x)),
+                }
+            }
             17 => {
                 // IMA ADPCM
                 match bits_per_sample {
-                    // 4 => Sample::ImaADPCM(ImaAdpcm {}), Unusable for now
+                    4 => Sample::ImaADPCM(ImaADPCM {}),
                     x => return Err(PCMError::UnknownBitsPerSample(*x)),
                 }
             }
@@ -167,6 +1227,16 @@ impl Sample {
             Sample::Float(_) => 0,
             Sample::DoubleFloat(_) => 0,
             Sample::ImaADPCM(_) => 4,
+            Sample::ALaw(_) => 2,
+            Sample::MuLaw(_) => 2,
+        }
+    }
+    /// Returns the block align to write in the format chunk of a Wave file, which is the size of a block for compressed types
+    pub fn wave_get_block_align(&self, nb_channels: u16) -> u16 {
+        if self.is_compressed() {
+            ima_adpcm::default_block_align(nb_channels)
+        } else {
+            nb_channels.saturating_mul(self.get_binary_size() / 8)
         }
     }
     /// Returns best format to use when writing this type to a Wave file
@@ -180,6 +1250,876 @@ impl Sample {
             Sample::Float(_) => 3,
             Sample::DoubleFloat(_) => 3,
             Sample::ImaADPCM(_) => 17,
+            Sample::ALaw(_) => 6,
+            Sample::MuLaw(_) => 7,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelPeak, WaveFrameReader, WaveImage};
+    use error::PCMError;
+    use ez_io::WriteE;
+    use ima_adpcm;
+    use sample_types::{ALaw, I24, ImaADPCM, MuLaw};
+    use std::convert::TryFrom;
+    use std::io::{Cursor, Write};
+    use {BTreeMap, BroadcastInfo, CuePoint, Frame, LoopInfo, PCMParameters, Sample, SpeakerPosition, PCM};
+    #[test]
+    fn stream_frames() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..100i16)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i), Sample::Signed16bits(-i)],
+                })
+                .collect(),
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        wave.set_position(0);
+        let reader = WaveFrameReader::new(wave).unwrap();
+        assert_eq!(reader.parameters().nb_channels, 2);
+        let mut nb_frames = 0;
+        for (i, frame) in reader.enumerate() {
+            match frame.unwrap().samples[1] {
+                Sample::Signed16bits(v) => assert_eq!(v, -(i as i16)),
+                _ => panic!(),
+            }
+            nb_frames += 1;
+        }
+        assert_eq!(nb_frames, 100);
+    }
+    #[test]
+    fn import_sampler_loops() {
+        let mut wave = Cursor::new(Vec::new());
+        wave.write_all(b"RIFF").unwrap();
+        wave.write_le_to_u32(4 + 24 + 48 + 92).unwrap();
+        wave.write_all(b"WAVEfmt ").unwrap();
+        wave.write_le_to_u32(16).unwrap();
+        wave.write_le_to_u16(1).unwrap();
+        wave.write_le_to_u16(2).unwrap();
+        wave.write_le_to_u32(8000).unwrap();
+        wave.write_le_to_u32(32000).unwrap();
+        wave.write_le_to_u16(4).unwrap();
+        wave.write_le_to_u16(16).unwrap();
+        wave.write_all(b"data").unwrap();
+        wave.write_le_to_u32(40).unwrap();
+        wave.write_all(&[0u8; 40]).unwrap();
+        wave.write_all(b"smpl").unwrap();
+        wave.write_le_to_u32(36 + 48).unwrap();
+        for _ in 0..7 {
+            wave.write_le_to_u32(0).unwrap();
+        }
+        wave.write_le_to_u32(2).unwrap(); // Number of loops
+        wave.write_le_to_u32(0).unwrap();
+        // Loop expressed in frames
+        for v in &[0, 0, 2, 8, 0, 0] {
+            wave.write_le_to_u32(*v).unwrap();
+        }
+        // Loop expressed in bytes
+        for v in &[1, 0, 8, 36, 0, 0] {
+            wave.write_le_to_u32(*v).unwrap();
         }
+        wave.set_position(0);
+        let pcm = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(pcm.frames.len(), 10);
+        let loops = pcm.loop_info.unwrap();
+        assert_eq!(loops.len(), 2);
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (2, 8));
+        assert_eq!((loops[1].loop_start, loops[1].loop_end), (2, 9));
+        // A sampler chunk claiming to be huge fails on the first missing loop
+        let mut bytes = wave.into_inner();
+        bytes[88..92].copy_from_slice(&[0xFF; 4]);
+        bytes[120..124].copy_from_slice(&[0xFF; 4]);
+        bytes.truncate(136);
+        assert!(PCM::from_wave_bytes(&bytes).is_err());
+    }
+    #[test]
+    fn export_sampler_loops() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Unsigned8bits(0),
+                channel_mask: None,
+            },
+            loop_info: Some(vec![
+                LoopInfo {
+                    loop_start: 10,
+                    loop_end: 50,
+                },
+                LoopInfo {
+                    loop_start: 60,
+                    loop_end: 99,
+                },
+            ]),
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..100u8)
+                .map(|i| Frame {
+                    samples: vec![Sample::Unsigned8bits(i)],
+                })
+                .collect(),
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        wave.set_position(0);
+        let imported = PCM::wave_import_file(&mut wave).unwrap();
+        let loops = imported.loop_info.unwrap();
+        assert_eq!(loops.len(), 2);
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (10, 50));
+        assert_eq!((loops[1].loop_start, loops[1].loop_end), (60, 99));
+        let mut no_rate = pcm;
+        no_rate.parameters.sample_rate = 0;
+        match no_rate.to_wave_bytes() {
+            Err(PCMError::InvalidParameters(_)) => {}
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn skip_unknown_chunks() {
+        let mut wave = Cursor::new(Vec::new());
+        wave.write_all(b"RIFF").unwrap();
+        wave.write_le_to_u32(4 + 14 + 12 + 24 + 12).unwrap();
+        wave.write_all(b"WAVE").unwrap();
+        wave.write_all(b"bext").unwrap();
+        wave.write_le_to_u32(5).unwrap();
+        wave.write_all(&[1, 2, 3, 4, 5, 0]).unwrap();
+        wave.write_all(b"LIST").unwrap();
+        wave.write_le_to_u32(4).unwrap();
+        wave.write_all(b"INFO").unwrap();
+        wave.write_all(b"fmt ").unwrap();
+        wave.write_le_to_u32(16).unwrap();
+        wave.write_le_to_u16(1).unwrap();
+        wave.write_le_to_u16(1).unwrap();
+        wave.write_le_to_u32(8000).unwrap();
+        wave.write_le_to_u32(16000).unwrap();
+        wave.write_le_to_u16(2).unwrap();
+        wave.write_le_to_u16(16).unwrap();
+        wave.write_all(b"data").unwrap();
+        wave.write_le_to_u32(4).unwrap();
+        wave.write_le_to_i16(1234).unwrap();
+        wave.write_le_to_i16(-1234).unwrap();
+        wave.set_position(0);
+        let pcm = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(pcm.frames.len(), 2);
+        match pcm.frames[1].samples[0] {
+            Sample::Signed16bits(v) => assert_eq!(v, -1234),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn odd_data_chunk_size() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Unsigned8bits(0),
+                channel_mask: None,
+            },
+            loop_info: Some(vec![LoopInfo {
+                loop_start: 0,
+                loop_end: 2,
+            }]),
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..3u8)
+                .map(|i| Frame {
+                    samples: vec![Sample::Unsigned8bits(i)],
+                })
+                .collect(),
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        assert_eq!(wave.get_ref().len() % 2, 0);
+        wave.set_position(0);
+        let imported = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(imported.frames.len(), 3);
+        let loops = imported.loop_info.unwrap();
+        assert_eq!((loops[0].loop_start, loops[0].loop_end), (0, 2));
+    }
+    #[test]
+    fn truncated_data_chunk() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..4)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i)],
+                })
+                .collect(),
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        let mut data = wave.into_inner();
+        data.truncate(data.len() - 3);
+        match PCM::wave_import_file(&mut Cursor::new(data)) {
+            Err(PCMError::ParseError {
+                offset,
+                chunk,
+                kind,
+            }) => {
+                assert_eq!(offset, 36);
+                assert_eq!(&chunk, b"data");
+                match *kind {
+                    PCMError::TruncatedData {
+                        expected: 8,
+                        actual: 5,
+                    } => {}
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn format_chunk_sizes() {
+        // Format chunk with a Size of the extra info of 0, then one with 4 bytes of unknown extra info
+        for &(chunk_size, extra) in &[(18u32, &[0u8, 0][..]), (22, &[4, 0, 1, 2, 3, 4][..])] {
+            let mut wave = Cursor::new(Vec::new());
+            wave.write_all(b"RIFF").unwrap();
+            wave.write_le_to_u32(4 + 8 + chunk_size + 8 + 4).unwrap();
+            wave.write_all(b"WAVEfmt ").unwrap();
+            wave.write_le_to_u32(chunk_size).unwrap();
+            wave.write_le_to_u16(1).unwrap();
+            wave.write_le_to_u16(1).unwrap();
+            wave.write_le_to_u32(8000).unwrap();
+            wave.write_le_to_u32(16000).unwrap();
+            wave.write_le_to_u16(2).unwrap();
+            wave.write_le_to_u16(16).unwrap();
+            wave.write_all(extra).unwrap();
+            wave.write_all(b"data").unwrap();
+            wave.write_le_to_u32(4).unwrap();
+            wave.write_le_to_i16(1).unwrap();
+            wave.write_le_to_i16(-1).unwrap();
+            wave.set_position(0);
+            let pcm = PCM::wave_import_file(&mut wave).unwrap();
+            assert_eq!(pcm.frames.len(), 2);
+            assert_eq!(pcm.frames[1].samples[0], Sample::Signed16bits(-1));
+        }
+        let mut wave = Cursor::new(Vec::new());
+        wave.write_all(b"RIFF").unwrap();
+        wave.write_le_to_u32(4 + 8 + 14).unwrap();
+        wave.write_all(b"WAVEfmt ").unwrap();
+        wave.write_le_to_u32(14).unwrap();
+        wave.write_all(&[0u8; 14]).unwrap();
+        wave.set_position(0);
+        assert!(PCM::wave_import_file(&mut wave).is_err());
+    }
+    #[test]
+    fn import_rifx() {
+        let mut wave = Cursor::new(Vec::new());
+        wave.write_all(b"RIFX").unwrap();
+        wave.write_be_to_u32(4 + 8 + 16 + 8 + 8 + 8 + 12).unwrap();
+        wave.write_all(b"WAVEfmt ").unwrap();
+        wave.write_be_to_u32(16).unwrap();
+        wave.write_be_to_u16(1).unwrap();
+        wave.write_be_to_u16(2).unwrap();
+        wave.write_be_to_u32(8000).unwrap();
+        wave.write_be_to_u32(32000).unwrap();
+        wave.write_be_to_u16(4).unwrap();
+        wave.write_be_to_u16(16).unwrap();
+        wave.write_all(b"data").unwrap();
+        wave.write_be_to_u32(8).unwrap();
+        for &v in &[1i16, -2, 0x1234, -0x1234] {
+            wave.write_be_to_i16(v).unwrap();
+        }
+        wave.write_all(b"fact").unwrap();
+        wave.write_be_to_u32(4).unwrap();
+        wave.write_be_to_u32(2).unwrap();
+        wave.write_all(b"JUNK").unwrap();
+        wave.write_be_to_u32(4).unwrap();
+        wave.write_all(&[0u8; 4]).unwrap();
+        wave.set_position(0);
+        let pcm = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(pcm.parameters.sample_rate, 8000);
+        assert_eq!(
+            pcm.frames,
+            vec![
+                Frame {
+                    samples: vec![Sample::Signed16bits(1), Sample::Signed16bits(-2)],
+                },
+                Frame {
+                    samples: vec![Sample::Signed16bits(0x1234), Sample::Signed16bits(-0x1234)],
+                },
+            ]
+        );
+        // Same header for 24 bits mono, where 3 bytes are reversed
+        let mut bytes = wave.into_inner();
+        bytes[23] = 1;
+        bytes[33] = 3;
+        bytes[35] = 24;
+        // The last 2 bytes are a partial frame
+        bytes[44..50].copy_from_slice(&[0x12, 0x34, 0x56, 0xFF, 0xFF, 0xFE]);
+        let pcm = PCM::from_wave_bytes(&bytes).unwrap();
+        assert_eq!(
+            pcm.frames[0].samples[0],
+            Sample::Signed24bits(I24 { value: 0x123456 })
+        );
+        assert_eq!(
+            pcm.frames[1].samples[0],
+            Sample::Signed24bits(I24 { value: -2 })
+        );
+        assert_eq!(pcm.frames.len(), 2);
+    }
+    #[test]
+    fn import_extensible() {
+        let mut wave = Cursor::new(Vec::new());
+        wave.write_all(b"RIFF").unwrap();
+        wave.write_le_to_u32(4 + 48 + 8 + 24).unwrap();
+        wave.write_all(b"WAVEfmt ").unwrap();
+        wave.write_le_to_u32(40).unwrap();
+        wave.write_le_to_u16(0xFFFE).unwrap();
+        wave.write_le_to_u16(6).unwrap();
+        wave.write_le_to_u32(48000).unwrap();
+        wave.write_le_to_u32(48000 * 24).unwrap();
+        wave.write_le_to_u16(24).unwrap();
+        wave.write_le_to_u16(32).unwrap();
+        wave.write_le_to_u16(22).unwrap();
+        wave.write_le_to_u16(32).unwrap();
+        wave.write_le_to_u32(0x3F).unwrap(); // 5.1
+        wave.write_all(&[
+            0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38,
+            0x9B, 0x71,
+        ]).unwrap(); // IEEE Float
+        wave.write_all(b"data").unwrap();
+        wave.write_le_to_u32(24).unwrap();
+        for i in 0..6 {
+            wave.write_le_to_f32(i as f32 / 10f32).unwrap();
+        }
+        wave.set_position(0);
+        let pcm = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(pcm.parameters.channel_mask, Some(0x3F));
+        assert_eq!(pcm.parameters.nb_channels, 6);
+        assert_eq!(pcm.frames.len(), 1);
+        match pcm.frames[0].samples[5] {
+            Sample::Float(v) => assert_eq!(v, 0.5),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn export_extensible() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 48000,
+                nb_channels: 6,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![Frame {
+                samples: (0..6).map(Sample::Signed16bits).collect(),
+            }],
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        wave.set_position(0);
+        let imported = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(imported.parameters.channel_mask, Some(0x3F));
+        let positions = imported.parameters.channel_positions();
+        assert_eq!(positions[2], SpeakerPosition::FrontCenter);
+        assert_eq!(positions[3], SpeakerPosition::LowFrequency);
+        match imported.frames[0].samples[5] {
+            Sample::Signed16bits(v) => assert_eq!(v, 5),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn markers_round_trip() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: vec![
+                CuePoint {
+                    id: 1,
+                    position: 3,
+                    label: Some(String::from("Verse")),
+                },
+                CuePoint {
+                    id: 2,
+                    position: 7,
+                    label: None,
+                },
+                CuePoint {
+                    id: 5,
+                    position: 9,
+                    label: Some(String::from("End")),
+                },
+            ],
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..10)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i)],
+                })
+                .collect(),
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        wave.set_position(0);
+        let imported = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(imported.frames.len(), 10);
+        assert_eq!(imported.markers.len(), 3);
+        for (original, imported) in pcm.markers.iter().zip(imported.markers.iter()) {
+            assert_eq!(original.id, imported.id);
+            assert_eq!(original.position, imported.position);
+            assert_eq!(original.label, imported.label);
+        }
+    }
+    #[test]
+    fn probe() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 1000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![
+                Frame {
+                    samples: vec![Sample::Signed16bits(0), Sample::Signed16bits(0)],
+                };
+                1500
+            ],
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        wave.set_position(0);
+        let info = PCM::probe_wave(&mut wave).unwrap();
+        assert_eq!(info.parameters.nb_channels, 2);
+        assert_eq!(info.nb_frames, 1500);
+        assert_eq!(info.get_audio_duration().as_millis(), 1500);
+        assert_eq!(wave.position(), wave.get_ref().len() as u64);
+        assert_eq!((info.data_offset, info.data_size, info.block_align), (44, 6000, 4));
+        // Jump straight to the last frame
+        wave.set_position(info.data_offset + 1499 * u64::from(info.block_align));
+        assert_eq!(wave.get_ref().len() as u64 - wave.position(), 4);
+        let mut ima = pcm.clone();
+        ima.parameters.sample_type = Sample::ImaADPCM(ImaADPCM {});
+        let mut wave = Cursor::new(Vec::new());
+        ima.wave_export_file(&mut wave).unwrap();
+        wave.set_position(0);
+        let info = PCM::probe_wave(&mut wave).unwrap();
+        // The fact chunk tells the real length, the last block being padded up to 505 frames
+        assert_eq!(info.nb_frames, 1500);
+        wave.set_position(0);
+        let imported = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(imported.frames.len(), 1500);
+        // Decode the third block on its own
+        let offsets = info.adpcm_block_offsets();
+        assert_eq!(offsets.len(), 3);
+        assert_eq!(offsets[1] - offsets[0], u64::from(info.block_align));
+        let block_start = offsets[2] as usize;
+        let block = &wave.get_ref()[block_start..block_start + info.block_align as usize];
+        let frames = ima_adpcm::decode(block, 2, info.block_align).unwrap();
+        assert_eq!(frames[..1500 - 1010], imported.frames[1010..]);
+        assert!(PCM::probe_wave(&mut Cursor::new(pcm.to_wave_bytes().unwrap()))
+            .unwrap()
+            .adpcm_block_offsets()
+            .is_empty());
+    }
+    #[test]
+    fn adpcm_block_align() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::ImaADPCM(ImaADPCM {}),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..100)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i * 100)],
+                })
+                .collect(),
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file_adpcm(&mut wave, 36).unwrap();
+        wave.set_position(0);
+        let imported = PCM::wave_import_file(&mut wave).unwrap();
+        assert_eq!(imported.frames.len(), 100);
+        // Block Align field of the format chunk
+        assert_eq!(&wave.get_ref()[32..34], &[36, 0]);
+        let mut wave = Cursor::new(Vec::new());
+        assert!(pcm.wave_export_file_adpcm(&mut wave, 6).is_err());
+        assert!(wave.get_ref().is_empty());
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        assert_eq!(&wave.get_ref()[32..34], &[0, 1]);
+        assert_eq!(Sample::ImaADPCM(ImaADPCM {}).wave_get_block_align(2), 512);
+        assert_eq!(Sample::Signed16bits(0).wave_get_block_align(2), 4);
+    }
+    #[test]
+    fn streamed_data_chunk() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..5)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i)],
+                })
+                .collect(),
+        };
+        let mut wave = Cursor::new(Vec::new());
+        pcm.wave_export_file(&mut wave).unwrap();
+        let mut data = wave.into_inner();
+        // Unknown sizes, and a frame cut in the middle
+        data[4..8].copy_from_slice(&[0xFF; 4]);
+        data[40..44].copy_from_slice(&[0xFF; 4]);
+        data.push(0x7F);
+        let imported = PCM::wave_import_file(&mut Cursor::new(data)).unwrap();
+        assert_eq!(imported.frames, pcm.frames);
+    }
+    #[test]
+    fn bytes_round_trip() {
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 2,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..5)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i), Sample::Signed16bits(-i)],
+                })
+                .collect(),
+        };
+        let bytes = pcm.to_wave_bytes().unwrap();
+        assert_eq!(bytes.len(), 44 + 20);
+        assert_eq!(PCM::from_wave_bytes(&bytes).unwrap(), pcm);
+        assert!(PCM::from_wave_bytes(&bytes[..10]).is_err());
+    }
+    #[test]
+    fn broadcast_info_round_trip() {
+        let mut umid = [0u8; 64];
+        umid[0] = 0x06;
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 48000,
+                nb_channels: 1,
+                sample_type: Sample::Signed16bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: Some(BroadcastInfo {
+                description: String::from("Take 3"),
+                originator: String::from("Recorder"),
+                originator_reference: String::new(),
+                origination_date: String::from("2019-05-04"),
+                origination_time: String::from("13-45-00"),
+                time_reference: 0x1_0000_0010,
+                version: 2,
+                umid,
+                loudness: [-2300, 500, -100, -1800, -2000],
+                coding_history: String::from("A=PCM,F=48000,W=16,M=mono\r\n"),
+            }),
+            tags: BTreeMap::new(),
+            frames: (0..4)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i)],
+                })
+                .collect(),
+        };
+        let bytes = pcm.to_wave_bytes().unwrap();
+        assert_eq!(&bytes[12..16], b"bext");
+        let imported = PCM::from_wave_bytes(&bytes).unwrap();
+        assert_eq!(imported, pcm);
+        let sliced = imported.slice(2, 4).unwrap();
+        assert_eq!(sliced.broadcast_info.unwrap().time_reference, 0x1_0000_0012);
+    }
+    #[test]
+    fn g711_round_trip() {
+        for law in &[Sample::ALaw(ALaw {}), Sample::MuLaw(MuLaw {})] {
+            let pcm = PCM {
+                parameters: PCMParameters {
+                    sample_rate: 8000,
+                    nb_channels: 1,
+                    sample_type: law.clone(),
+                    channel_mask: None,
+                },
+                loop_info: None,
+                markers: Vec::new(),
+                broadcast_info: None,
+                tags: BTreeMap::new(),
+                frames: [0i16, 1000, -1000, 32000, -32000]
+                    .iter()
+                    .map(|v| Frame {
+                        samples: vec![Sample::Signed16bits(*v)],
+                    })
+                    .collect(),
+            };
+            let bytes = pcm.to_wave_bytes().unwrap();
+            // Format, Block Align, Bits per Sample and Size of the extra info
+            assert_eq!(bytes[20], law.wave_get_best_format() as u8);
+            assert_eq!(&bytes[32..38], &[1, 0, 8, 0, 0, 0]);
+            let imported = PCM::from_wave_bytes(&bytes).unwrap();
+            assert!(imported.parameters.sample_type.is_same_type(law));
+            assert_eq!(imported.frames.len(), 5);
+            for (original, imported) in pcm.frames.iter().zip(imported.frames.iter()) {
+                match (&original.samples[0], &imported.samples[0]) {
+                    (Sample::Signed16bits(o), Sample::Signed16bits(i)) => {
+                        assert!((i32::from(*o) - i32::from(*i)).abs() <= 1024)
+                    }
+                    _ => panic!(),
+                }
+            }
+        }
+    }
+    #[test]
+    fn info_tags_round_trip() {
+        let mut tags = BTreeMap::new();
+        tags.insert(*b"INAM", String::from("Door slam"));
+        tags.insert(*b"IART", String::from("Foley team"));
+        tags.insert(*b"ICMT", String::new());
+        let pcm = PCM {
+            parameters: PCMParameters {
+                sample_rate: 8000,
+                nb_channels: 1,
+                sample_type: Sample::Unsigned8bits(0),
+                channel_mask: None,
+            },
+            loop_info: None,
+            markers: vec![CuePoint {
+                id: 1,
+                position: 1,
+                label: Some(String::from("Hit")),
+            }],
+            broadcast_info: None,
+            tags,
+            frames: (0..3)
+                .map(|i| Frame {
+                    samples: vec![Sample::Unsigned8bits(i)],
+                })
+                .collect(),
+        };
+        let imported = PCM::from_wave_bytes(&pcm.to_wave_bytes().unwrap()).unwrap();
+        assert_eq!(imported, pcm);
+    }
+    #[test]
+    fn zero_header_fields() {
+        let pcm = PCM {
+            parameters: PCMParameters::new(8000, 1, Sample::Signed16bits(0)).unwrap(),
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: vec![
+                Frame {
+                    samples: vec![Sample::Signed16bits(0)],
+                };
+                4
+            ],
+        };
+        let mut bytes = pcm.to_wave_bytes().unwrap();
+        // Number of Channels in the format chunk
+        bytes[22] = 0;
+        match PCM::from_wave_bytes(&bytes) {
+            Err(PCMError::ParseError { kind, .. }) => match *kind {
+                PCMError::InvalidHeader(_) => {}
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+        // Bits per Sample
+        bytes[22] = 1;
+        bytes[34] = 0;
+        assert!(PCM::wave_import_file(&mut Cursor::new(&bytes)).is_err());
+        assert!(PCM::probe_wave(&mut Cursor::new(&bytes)).is_err());
+    }
+    #[test]
+    fn huge_frames() {
+        let pcm = PCM::empty(PCMParameters::new(8000, 32768, Sample::Signed16bits(0)).unwrap());
+        assert!(pcm.to_wave_bytes().is_err());
+        let mono = PCM::from_iter_f64(
+            PCMParameters::new(8000, 1, Sample::Signed16bits(0)).unwrap(),
+            vec![0f64; 4],
+        )
+        .unwrap();
+        let mut bytes = mono.to_wave_bytes().unwrap();
+        // 32768 channels of 16 bits make frames of 65536 bytes, bigger than the audio data
+        bytes[22..24].copy_from_slice(&[0x00, 0x80]);
+        let imported = PCM::from_wave_bytes(&bytes).unwrap();
+        assert_eq!(imported.parameters.nb_channels, 32768);
+        assert!(imported.frames.is_empty());
+        assert_eq!(PCM::probe_wave(&mut Cursor::new(&bytes)).unwrap().nb_frames, 0);
+    }
+    #[test]
+    fn wave_image() {
+        let mut pcm = PCM {
+            parameters: PCMParameters::new(8000, 1, Sample::Unsigned8bits(0)).unwrap(),
+            loop_info: Some(vec![LoopInfo {
+                loop_start: 0,
+                loop_end: 2,
+            }]),
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..3)
+                .map(|i| Frame {
+                    samples: vec![Sample::Unsigned8bits(i)],
+                })
+                .collect(),
+        };
+        let image = WaveImage::try_from(&pcm).unwrap();
+        assert_eq!((image.data_offset(), image.data.len()), (44, 3));
+        assert_eq!(image.data, vec![0, 1, 2]);
+        // Padding byte and sampler chunk
+        assert_eq!(image.trailer.len(), 1 + 8 + 60);
+        let mut written = Vec::new();
+        image.write_to(&mut written).unwrap();
+        assert_eq!(written, pcm.to_wave_bytes().unwrap());
+        assert_eq!(image.size(), written.len());
+        assert_eq!(image.into_bytes(), written);
+        pcm.frames.push(Frame {
+            samples: Vec::new(),
+        });
+        assert!(WaveImage::try_from(&pcm).is_err());
+    }
+    #[test]
+    fn peak_chunk() {
+        let mut pcm = PCM {
+            parameters: PCMParameters::new(8000, 2, Sample::Float(0f32)).unwrap(),
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: [(0.25, 0f32), (-0.75, 0.5), (0.5, 0.5)]
+                .iter()
+                .map(|&(l, r)| Frame {
+                    samples: vec![Sample::Float(l), Sample::Float(r)],
+                })
+                .collect(),
+        };
+        let bytes = pcm.to_wave_bytes().unwrap();
+        let info = PCM::probe_wave(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(
+            info.peaks.unwrap(),
+            vec![
+                ChannelPeak {
+                    value: 0.75,
+                    position: 1,
+                },
+                ChannelPeak {
+                    value: 0.5,
+                    position: 1,
+                },
+            ]
+        );
+        assert_eq!(PCM::from_wave_bytes(&bytes).unwrap(), pcm);
+        pcm.ensure_sample_type(Sample::Signed16bits(0));
+        let bytes = pcm.to_wave_bytes().unwrap();
+        assert!(PCM::probe_wave(&mut Cursor::new(&bytes)).unwrap().peaks.is_none());
+        // A PEAK chunk claiming to be huge fails on the first missing entry
+        let mut wave = Cursor::new(Vec::new());
+        wave.write_all(b"RIFF").unwrap();
+        wave.write_le_to_u32(<u32>::max_value()).unwrap();
+        wave.write_all(b"WAVEPEAK").unwrap();
+        wave.write_le_to_u32(<u32>::max_value()).unwrap();
+        wave.write_all(&[0u8; 12]).unwrap();
+        wave.set_position(0);
+        assert!(PCM::probe_wave(&mut wave).is_err());
+    }
+    #[test]
+    fn rf64_round_trip() {
+        let pcm = PCM {
+            parameters: PCMParameters::new(8000, 1, Sample::Signed16bits(0)).unwrap(),
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..5)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i)],
+                })
+                .collect(),
+        };
+        let mut writer = Cursor::new(Vec::new());
+        pcm.wave_export(&mut writer, 2, true).unwrap();
+        let mut bytes = writer.into_inner();
+        assert_eq!(&bytes[..4], b"RF64");
+        assert_eq!(&bytes[12..16], b"ds64");
+        assert_eq!(bytes.len(), 44 + 36 + 10);
+        assert_eq!(PCM::from_wave_bytes(&bytes).unwrap(), pcm);
+        bytes[..4].copy_from_slice(b"BW64");
+        assert_eq!(PCM::from_wave_bytes(&bytes).unwrap(), pcm);
+        bytes[..4].copy_from_slice(b"RIFX");
+        assert!(PCM::from_wave_bytes(&bytes).is_err());
+    }
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_import() {
+        let mut pcm = PCM {
+            parameters: PCMParameters::new(44100, 2, Sample::Signed16bits(0)).unwrap(),
+            loop_info: None,
+            markers: Vec::new(),
+            broadcast_info: None,
+            tags: BTreeMap::new(),
+            frames: (0..100_000)
+                .map(|i| Frame {
+                    samples: vec![Sample::Signed16bits(i as i16), Sample::Signed16bits(-i as i16)],
+                })
+                .collect(),
+        };
+        let bytes = pcm.to_wave_bytes().unwrap();
+        let imported = PCM::wave_import_file_parallel(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(imported, pcm);
+        // ADPCM blocks are not cut between batches
+        pcm.parameters.sample_type = Sample::ImaADPCM(ImaADPCM {});
+        let bytes = pcm.to_wave_bytes().unwrap();
+        assert_eq!(
+            PCM::wave_import_file_parallel(&mut Cursor::new(&bytes)).unwrap(),
+            PCM::from_wave_bytes(&bytes).unwrap()
+        );
     }
 }